@@ -0,0 +1,33 @@
+//! Compares reading slots one by one (`Fact::slot`, which looks the slot up
+//! in the template on every call) against reading them through `Fact::accessor`
+extern crate clips;
+
+use clips::{Environment, Builder};
+use std::time::Instant;
+
+const ITERATIONS: usize = 100_000;
+
+fn main() {
+    let env = Environment::new().unwrap();
+    env.load_string("(deftemplate f (slot a) (slot b) (slot c) (slot d) (slot e) (slot f) (slot g) (slot h))").unwrap();
+    let fb = env.new_fact_builder("f");
+    for (i, slot) in ["a", "b", "c", "d", "e", "f", "g", "h"].iter().enumerate() {
+        fb.put(slot, i as i64).unwrap();
+    }
+    let fact = fb.build().unwrap();
+
+    let start = Instant::now();
+    let mut sum = 0;
+    for _ in 0..ITERATIONS {
+        sum += fact.slot("g").as_number::<i64>().unwrap() + fact.slot("h").as_number::<i64>().unwrap();
+    }
+    println!("slot:     {:?} ({})", start.elapsed(), sum);
+
+    let start = Instant::now();
+    let mut sum = 0;
+    let accessor = fact.accessor();
+    for _ in 0..ITERATIONS {
+        sum += accessor.get::<i64, _>("g").unwrap() + accessor.get::<i64, _>("h").unwrap();
+    }
+    println!("accessor: {:?} ({})", start.elapsed(), sum);
+}
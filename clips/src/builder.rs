@@ -0,0 +1,54 @@
+use super::value::EnvAllocatable;
+use sys;
+
+/// Common interface of builders (such as `FactBuilder`),
+/// allowing generic code to build any kind of CLIPS
+/// objects out of slots
+pub trait Builder : Sized {
+    /// The result of building
+    type Output;
+    /// The error returned when building fails
+    type Error;
+
+    /// Put a slot
+    fn put<S: AsRef<str>, V: EnvAllocatable>(&self, slot: S, value: V) -> Result<(), sys::PutSlotError>;
+
+    /// Build the object, consuming the builder
+    fn build(self) -> Result<Self::Output, Self::Error>;
+
+    /// Abort building
+    fn abort(self);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    fn build_two<B: Builder>(first: B, second: B) -> Result<(B::Output, B::Output), B::Error> {
+        first.put("a", 1).unwrap();
+        second.put("a", 2).unwrap();
+        Ok((first.build()?, second.build()?))
+    }
+
+    #[test]
+    fn generic_build() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        "#).unwrap();
+        let (f1, f2) = build_two(env.new_fact_builder("f1"), env.new_fact_builder("f1")).unwrap();
+        assert_eq!(env.number_of_facts(), 2);
+        assert_eq!(i64::value(&f1.slot("a")), Some(1));
+        assert_eq!(i64::value(&f2.slot("a")), Some(2));
+    }
+
+    #[test]
+    fn generic_abort() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        "#).unwrap();
+        Builder::abort(env.new_fact_builder("f1"));
+        assert_eq!(env.number_of_facts(), 0);
+    }
+}
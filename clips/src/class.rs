@@ -0,0 +1,88 @@
+use super::Environment;
+use sys;
+use std::ffi::CStr;
+use std::marker::PhantomData;
+
+/// Represents a class (defclass)
+pub struct Class<'a> {
+    pub(crate) class: *mut sys::Defclass,
+    pub(crate) env: PhantomData<&'a Environment>,
+}
+
+/// Message handler (defmessage-handler) of a class
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MessageHandler {
+    pub name: String,
+    /// Handler type (`primary`, `around`, `before` or `after`)
+    pub handler_type: String,
+}
+
+impl<'a> Class<'a> {
+
+    /// Class name
+    pub fn name(&self) -> &str {
+        let name = unsafe { CStr::from_ptr(sys::DefclassName(self.class)) };
+        name.to_str().unwrap()
+    }
+
+    /// Returns message handlers defined directly in this class
+    /// (not including inherited ones)
+    pub fn message_handlers(&self) -> Vec<MessageHandler> {
+        let mut handlers = vec![];
+        let mut index = unsafe { sys::GetNextDefmessageHandler(self.class, 0) };
+        while index != 0 {
+            let (name, handler_type) = unsafe {
+                (CStr::from_ptr(sys::DefmessageHandlerName(self.class, index)),
+                 CStr::from_ptr(sys::DefmessageHandlerType(self.class, index)))
+            };
+            handlers.push(MessageHandler {
+                name: String::from(name.to_str().unwrap()),
+                handler_type: String::from(handler_type.to_str().unwrap()),
+            });
+            index = unsafe { sys::GetNextDefmessageHandler(self.class, index) };
+        }
+        handlers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    #[test]
+    fn find_class() {
+        let env = Environment::new().unwrap();
+        env.load_string("(defclass C (is-a USER))").unwrap();
+        assert_eq!(env.find_class("C").unwrap().name(), "C");
+        assert!(env.find_class("D").is_none());
+    }
+
+    #[test]
+    fn message_handlers() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (defclass C (is-a USER) (slot x))
+        "#).unwrap();
+        env.define_message_handler("C", "get-double", &[], "(* ?self:x 2)").unwrap();
+        env.eval("(make-instance c1 of C (x 21))").unwrap();
+        assert_eq!(i64::value(&env.eval("(send [c1] get-double)").unwrap()), Some(42));
+        // slot accessors (get-x, put-x) are handlers, too
+        let handlers = env.find_class("C").unwrap().message_handlers();
+        assert!(handlers.contains(&MessageHandler {
+            name: String::from("get-double"),
+            handler_type: String::from("primary"),
+        }));
+    }
+
+    #[test]
+    fn message_handler_with_params() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (defclass C (is-a USER) (slot x))
+        "#).unwrap();
+        env.define_message_handler("C", "add", &["?y"], "(+ ?self:x ?y)").unwrap();
+        env.eval("(make-instance c1 of C (x 1))").unwrap();
+        assert_eq!(i64::value(&env.eval("(send [c1] add 2)").unwrap()), Some(3));
+        assert_eq!(env.define_message_handler("D", "add", &[], "1").unwrap_err(), LoadError::ParsingError);
+    }
+}
@@ -1,5 +1,6 @@
 use super::Environment;
-use super::value::{Value, EnvAllocatable};
+use super::value::{Type, Value, ValueAccess, EnvAllocatable, OwnedValue, MultifieldIter, ClipsDisplay};
+use super::builder::Builder;
 use sys;
 
 /// Template-based fact builder
@@ -8,7 +9,9 @@ pub struct FactBuilder<'a> {
     fb: *mut sys::FactBuilder,
 }
 
-use std::ffi::CString;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::collections::HashMap;
 
 /// Allows building facts from templates
 impl<'a> FactBuilder<'a> {
@@ -52,6 +55,12 @@ impl<'a> FactBuilder<'a> {
         }
     }
 
+    /// Assert the fact and retain it (see `Fact::retain`) at once,
+    /// consuming the builder
+    pub fn assert_retained(self) -> Result<RetainedFact<'a>, ()> {
+        self.assert().map(|fact| fact.retain())
+    }
+
     /// Abort fact building
     pub fn abort(self) {
         unsafe {
@@ -60,6 +69,23 @@ impl<'a> FactBuilder<'a> {
     }
 }
 
+impl<'a> Builder for FactBuilder<'a> {
+    type Output = Fact<'a>;
+    type Error = ();
+
+    fn put<S: AsRef<str>, V: EnvAllocatable>(&self, slot: S, value: V) -> Result<(), sys::PutSlotError> {
+        FactBuilder::put(self, slot, value)
+    }
+
+    fn build(self) -> Result<Self::Output, Self::Error> {
+        self.assert()
+    }
+
+    fn abort(self) {
+        FactBuilder::abort(self)
+    }
+}
+
 impl<'a> Drop for FactBuilder<'a> {
     fn drop(&mut self) {
         unsafe {
@@ -91,6 +117,13 @@ impl<'a> Fact<'a> {
         }
     }
 
+    /// Retract the fact, consuming it. Returns the slots
+    /// the fact had at the moment of retraction
+    /// (see `slot_map`)
+    pub fn retract_capturing(self) -> Result<Vec<(String, OwnedValue)>, sys::RetractError> {
+        let slots = self.slot_map();
+        self.retract().and(Ok(slots))
+    }
 
     pub fn slot<S: AsRef<str>>(&self, name: S) -> Value {
         let mut val : Value = unsafe { ::std::mem::zeroed() };
@@ -101,6 +134,171 @@ impl<'a> Fact<'a> {
         val
     }
 
+    /// Iterates over the contents of a multislot, converting each
+    /// element into `T` as it goes (`None` for incompatible elements)
+    ///
+    /// Yields nothing if there's no such slot or it is not a multislot
+    pub fn multislot_iter<'f, T: ValueAccess + 'f, S: AsRef<str>>(&'f self, name: S) -> impl Iterator<Item = Option<T>> + 'f {
+        let val = self.slot(name);
+        let iter = if unsafe { val.0.__bindgen_anon_1.value.is_null() } || val.type_of() != Type::Multifield {
+            None
+        } else {
+            Some(unsafe { MultifieldIter::new(val.0.__bindgen_anon_1.multifieldValue) })
+        };
+        iter.into_iter().flatten().map(|v| T::value(&v))
+    }
+
+    /// Fact's template
+    pub fn template(&self) -> Template<'a> {
+        Template {
+            env: self.1,
+            template: unsafe { sys::FactDeftemplate(self.0) },
+        }
+    }
+
+    /// Returns `true` if the fact's template has the given name
+    pub fn is_template<S: AsRef<str>>(&self, name: S) -> bool {
+        self.template().name() == name.as_ref()
+    }
+
+    /// Returns all slots of the fact with their values
+    /// detached from the environment, in the order of
+    /// their declaration in the template
+    pub fn slot_map(&self) -> Vec<(String, OwnedValue)> {
+        self.template().slot_names().into_iter()
+            .map(|name| {
+                let value = OwnedValue::value(&self.slot(&name)).unwrap_or(OwnedValue::Void);
+                (name, value)
+            })
+            .collect()
+    }
+
+    /// Retains the fact, so that it remains valid (even if retracted)
+    /// for as long as the returned `RetainedFact` exists
+    ///
+    /// Note that `slot` returns `FALSE` for retracted facts,
+    /// while `accessor` can still read their slots
+    pub fn retain(&self) -> RetainedFact<'a> {
+        unsafe {
+            sys::RetainFact(self.0)
+        }
+        RetainedFact(self.clone())
+    }
+
+    /// Returns an accessor for reading multiple slots of the fact
+    ///
+    /// Slot names are retrieved from the template once, so reading
+    /// many slots through the accessor avoids repeated lookups
+    pub fn accessor(&self) -> FactAccessor {
+        FactAccessor {
+            fact: self,
+            slots: self.template().slot_names().into_iter()
+                .enumerate()
+                .map(|(index, name)| (name, index))
+                .collect(),
+        }
+    }
+
+}
+
+/// Retained fact (see `Fact::retain`), released when dropped
+pub struct RetainedFact<'a>(Fact<'a>);
+
+impl<'a> Deref for RetainedFact<'a> {
+    type Target = Fact<'a>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<'a> Clone for RetainedFact<'a> {
+    fn clone(&self) -> Self {
+        self.0.retain()
+    }
+}
+
+impl<'a> Drop for RetainedFact<'a> {
+    fn drop(&mut self) {
+        unsafe {
+            sys::ReleaseFact((self.0).0)
+        }
+    }
+}
+
+/// Slot accessor (see `Fact::accessor`)
+pub struct FactAccessor<'f, 'a: 'f> {
+    fact: &'f Fact<'a>,
+    /// Slot indices by name
+    slots: HashMap<String, usize>,
+}
+
+impl<'f, 'a> FactAccessor<'f, 'a> {
+
+    /// Returns slot's value if the slot exists and is compatible
+    /// with `T`, otherwise `None`
+    pub fn get<T: ValueAccess, S: AsRef<str>>(&self, name: S) -> Option<T> {
+        let index = *self.slots.get(name.as_ref())?;
+        let val = unsafe {
+            *(*self.fact.0).theProposition.contents.as_ptr().add(index)
+        };
+        T::value(&Value(val))
+    }
+
+}
+
+#[cfg(feature = "json")]
+impl<'a> Fact<'a> {
+    /// Serializes the fact into a JSON object containing
+    /// template name and slots, such as
+    /// `{"template":"f1","slots":{"a":1,"b":"a"}}`
+    pub fn to_json(&self) -> String {
+        use serde::ser::{Serialize, Serializer, SerializeMap, SerializeStruct};
+
+        struct Slots(Vec<(String, OwnedValue)>);
+
+        impl Serialize for Slots {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut map = serializer.serialize_map(Some(self.0.len()))?;
+                for &(ref name, ref value) in self.0.iter() {
+                    map.serialize_entry(name, value)?;
+                }
+                map.end()
+            }
+        }
+
+        struct FactJson<'t>(&'t str, Slots);
+
+        impl<'t> Serialize for FactJson<'t> {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut fact = serializer.serialize_struct("Fact", 2)?;
+                fact.serialize_field("template", self.0)?;
+                fact.serialize_field("slots", &self.1)?;
+                fact.end()
+            }
+        }
+
+        let template = self.template();
+        ::serde_json::to_string(&FactJson(template.name(), Slots(self.slot_map())))
+            .expect("fact serialization shouldn't fail")
+    }
+}
+
+impl Value {
+    /// Returns the fact if the value is a fact address
+    pub fn as_fact<'env>(&self, env: &'env Environment) -> Option<Fact<'env>> {
+        match self.type_of() {
+            Type::FactAddress => Some(Fact(unsafe { self.0.__bindgen_anon_1.factValue }, env)),
+            _ => None,
+        }
+    }
+
+    /// Returns the facts if the value is a multifield
+    /// consisting of fact addresses only
+    pub fn as_facts<'env>(&self, env: &'env Environment) -> Option<Vec<Fact<'env>>> {
+        self.multifield_iter()
+            .and_then(|iter| iter.map(|v| v.as_fact(env)).collect())
+    }
 }
 
 impl<'a> EnvAllocatable for Fact<'a> {
@@ -204,6 +402,173 @@ impl<'a> Template<'a> {
     pub fn into_fact_iter(self) -> TemplateIter<'a> {
         TemplateIter::new(self.env, self.template)
     }
+
+    /// Template name
+    pub fn name(&self) -> &str {
+        let name = unsafe { CStr::from_ptr(sys::DeftemplateName(self.template)) };
+        name.to_str().unwrap()
+    }
+
+    /// Returns slot names in the order of their declaration
+    pub fn slot_names(&self) -> Vec<String> {
+        let mut val : Value = unsafe { ::std::mem::zeroed() };
+        unsafe {
+            sys::DeftemplateSlotNames(self.template, &mut val.0)
+        }
+        match OwnedValue::value(&val) {
+            Some(OwnedValue::Multifield(names)) => names.into_iter()
+                .filter_map(|name| match name {
+                    OwnedValue::Symbol(name) => Some(name),
+                    _ => None,
+                })
+                .collect(),
+            _ => vec![],
+        }
+    }
+
+    /// Returns minimum and maximum (`None` if unbounded) number of values
+    /// a slot can hold, or `None` if there's no such slot
+    ///
+    /// Single-field slots always hold exactly one value
+    pub fn slot_cardinality<S: AsRef<str>>(&self, slot: S) -> Option<(usize, Option<usize>)> {
+        let mut val : Value = unsafe { ::std::mem::zeroed() };
+        let c_string = CString::new(slot.as_ref()).unwrap();
+        if !unsafe { sys::DeftemplateSlotCardinality(self.template, c_string.as_ptr(), &mut val.0) } {
+            return None;
+        }
+        match OwnedValue::value(&val) {
+            Some(OwnedValue::Multifield(bounds)) => match bounds.as_slice() {
+                [] => Some((1, Some(1))),
+                [OwnedValue::Integer(min), OwnedValue::Integer(max)] => Some((*min as usize, Some(*max as usize))),
+                [OwnedValue::Integer(min), _] => Some((*min as usize, None)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns values allowed in a slot (as restricted by `allowed-values`,
+    /// `allowed-symbols` and similar attributes), or `None` if the slot
+    /// is unrestricted or there's no such slot
+    pub fn slot_allowed_values<S: AsRef<str>>(&self, slot: S) -> Option<Vec<OwnedValue>> {
+        let mut val : Value = unsafe { ::std::mem::zeroed() };
+        let c_string = CString::new(slot.as_ref()).unwrap();
+        if !unsafe { sys::DeftemplateSlotAllowedValues(self.template, c_string.as_ptr(), &mut val.0) } {
+            return None;
+        }
+        match OwnedValue::value(&val) {
+            Some(OwnedValue::Multifield(values)) => Some(values),
+            _ => None,
+        }
+    }
+}
+
+type Condition<'env> = Box<dyn Fn(&Value) -> bool + 'env>;
+
+/// Query over facts of a template (see `Environment::query`)
+pub struct FactQuery<'env> {
+    env: &'env Environment,
+    template: String,
+    conditions: Vec<(String, Condition<'env>)>,
+}
+
+impl<'env> FactQuery<'env> {
+
+    pub(crate) fn new<S: AsRef<str>>(env: &'env Environment, template: S) -> Self {
+        FactQuery {
+            env,
+            template: String::from(template.as_ref()),
+            conditions: vec![],
+        }
+    }
+
+    /// Only matches facts with slot's value equal to `value`
+    pub fn where_slot_eq<S, V>(mut self, slot: S, value: V) -> Self
+        where S: AsRef<str>, V: ValueAccess + PartialEq + 'env {
+        self.conditions.push((String::from(slot.as_ref()),
+                              Box::new(move |v| V::value(v).is_some_and(|v| v == value))));
+        self
+    }
+
+    /// Returns all matching facts
+    ///
+    /// If the template doesn't exist or doesn't have one of
+    /// the slots queried, no facts are returned
+    pub fn collect(self) -> Vec<Fact<'env>> {
+        let template = match self.env.find_template(&self.template) {
+            Some(template) => template,
+            None => return vec![],
+        };
+        let slots = template.slot_names();
+        if self.conditions.iter().any(|(slot, _)| !slots.contains(slot)) {
+            return vec![];
+        }
+        let conditions = self.conditions;
+        template.into_fact_iter()
+            .filter(|fact| conditions.iter().all(|(slot, condition)| condition(&fact.slot(slot))))
+            .collect()
+    }
+
+}
+
+/// Default value of a slot (see `SlotDef`)
+#[derive(Debug, Clone, PartialEq)]
+pub enum DefaultKind {
+    /// Value computed once, when the template is defined (`default`)
+    Static(OwnedValue),
+    /// Expression evaluated on every assertion (`default-dynamic`)
+    Dynamic(String),
+    /// No explicit default (`nil` for slots, empty for multislots)
+    None,
+}
+
+/// Slot definition for `Environment::define_template`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SlotDef {
+    pub name: String,
+    pub multislot: bool,
+    pub default: DefaultKind,
+}
+
+impl SlotDef {
+    /// Single-field slot without a default
+    pub fn slot<S: Into<String>>(name: S) -> Self {
+        SlotDef {
+            name: name.into(),
+            multislot: false,
+            default: DefaultKind::None,
+        }
+    }
+
+    /// Multifield slot without a default
+    pub fn multislot<S: Into<String>>(name: S) -> Self {
+        SlotDef {
+            multislot: true,
+            ..SlotDef::slot(name)
+        }
+    }
+
+    /// Sets slot's default
+    pub fn default(mut self, default: DefaultKind) -> Self {
+        self.default = default;
+        self
+    }
+}
+
+impl fmt::Display for SlotDef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({} {}", if self.multislot { "multislot" } else { "slot" }, self.name)?;
+        match self.default {
+            DefaultKind::Static(ref value) => {
+                f.write_str(" (default ")?;
+                value.fmt_clips(f)?;
+                f.write_str(")")?;
+            },
+            DefaultKind::Dynamic(ref expr) => write!(f, " (default-dynamic {})", expr)?,
+            DefaultKind::None => (),
+        }
+        f.write_str(")")
+    }
 }
 
 use std::ops::Deref;
@@ -214,6 +579,28 @@ pub trait Recoverable {
     fn recover(self) -> Self::T;
 }
 
+/// Asserted fact along with the number of rules
+/// fired right after asserting it
+pub struct Fired<T>(pub T, pub usize);
+
+impl<'env, T: Deref<Target=Fact<'env>>> Deref for Fired<T> {
+    type Target = Fact<'env>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+/// Type representing facts of a particular template
+pub trait FactType<'env> {
+    /// Asserted fact wrapper
+    type Asserted : Deref<Target=Fact<'env>>;
+    /// Template name
+    fn template_name() -> &'static str;
+    /// Wraps a fact of the template
+    fn wrap(fact: Fact<'env>) -> Self::Asserted;
+}
+
 /// Anything can be asserted as a fact
 pub trait Assertable<'env> {
     type T : Deref<Target=Fact<'env>>;
@@ -274,6 +661,32 @@ mod tests {
         assert_eq!(env.number_of_facts(), 2);
     }
 
+    #[test]
+    fn facts_in_multislot() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        (deftemplate f2 (multislot facts))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        let fact1 = fb.assert().unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 2).unwrap();
+        let fact2 = fb.assert().unwrap();
+        let fb = env.new_fact_builder("f2");
+        fb.put("facts", vec![fact1.clone(), fact2.clone()]).unwrap();
+        let fact = fb.assert().unwrap();
+        let facts = fact.slot("facts").as_facts(&env).unwrap();
+        assert_eq!(facts.iter().map(Fact::index).collect::<Vec<_>>(), vec![fact1.index(), fact2.index()]);
+        assert_eq!(i64::value(&facts[1].slot("a")), Some(2));
+        // not all elements are facts
+        let fb = env.new_fact_builder("f2");
+        fb.put("facts", vec![1]).unwrap();
+        let fact = fb.assert().unwrap();
+        assert!(fact.slot("facts").as_facts(&env).is_none());
+    }
+
     #[test]
     fn retract() {
         let env = Environment::new().unwrap();
@@ -290,6 +703,24 @@ mod tests {
         assert_eq!(env.number_of_facts(), 0);
     }
 
+    #[test]
+    fn retract_capturing() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a) (slot b))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        fb.put("b", "a").unwrap();
+        let fact = fb.assert().unwrap();
+        let slots = fact.retract_capturing().unwrap();
+        assert_eq!(env.number_of_facts(), 0);
+        assert_eq!(slots, vec![
+            (String::from("a"), OwnedValue::Integer(1)),
+            (String::from("b"), OwnedValue::String(String::from("a"))),
+        ]);
+    }
+
     #[test]
     fn access_fact_slot_value() {
         let env = Environment::new().unwrap();
@@ -378,4 +809,177 @@ mod tests {
         assert_eq!(facts.len(), 1);
     }
 
+    #[test]
+    fn template_name() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1)").unwrap();
+        assert_eq!(env.find_template("f1").unwrap().name(), "f1");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn fact_to_json() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot b) (slot a))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        fb.put("b", "a").unwrap();
+        let fact = fb.assert().unwrap();
+        assert_eq!(fact.to_json(), r#"{"template":"f1","slots":{"b":"a","a":1}}"#);
+    }
+
+    #[test]
+    fn template_slot_names() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot c) (slot a) (multislot b))
+        "#).unwrap();
+        let template = env.find_template("f1").unwrap();
+        assert_eq!(template.slot_names(), vec!["c", "a", "b"]);
+    }
+
+    #[test]
+    fn fact_slot_map() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot c) (slot a) (slot b))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        fb.put("b", "b").unwrap();
+        fb.put("c", Symbol("c")).unwrap();
+        let fact = fb.assert().unwrap();
+        assert_eq!(fact.slot_map(), vec![
+            (String::from("c"), OwnedValue::Symbol(String::from("c"))),
+            (String::from("a"), OwnedValue::Integer(1)),
+            (String::from("b"), OwnedValue::String(String::from("b"))),
+        ]);
+    }
+
+    #[test]
+    fn fact_accessor() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot c) (slot a) (multislot b))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        fb.put("b", vec![2, 3]).unwrap();
+        fb.put("c", "c").unwrap();
+        let fact = fb.assert().unwrap();
+        let accessor = fact.accessor();
+        assert_eq!(accessor.get::<i64, _>("a"), Some(1));
+        assert_eq!(accessor.get::<String, _>("c"), Some(String::from("c")));
+        assert_eq!(accessor.get::<OwnedValue, _>("b"),
+                   Some(OwnedValue::Multifield(vec![OwnedValue::Integer(2), OwnedValue::Integer(3)])));
+        assert_eq!(accessor.get::<i64, _>("c"), None);
+        assert_eq!(accessor.get::<i64, _>("d"), None);
+    }
+
+    #[test]
+    fn multislot_iter() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a) (multislot b))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", 1).unwrap();
+        fb.put("b", (1..1001).collect::<Vec<i64>>()).unwrap();
+        let fact = fb.assert().unwrap();
+        assert_eq!(fact.multislot_iter::<i64, _>("b").map(Option::unwrap).sum::<i64>(), 500500);
+        assert_eq!(fact.multislot_iter::<String, _>("b").next(), Some(None));
+        assert_eq!(fact.multislot_iter::<i64, _>("a").count(), 0);
+        assert_eq!(fact.multislot_iter::<i64, _>("c").count(), 0);
+    }
+
+    #[test]
+    fn template_slot_constraints() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (multislot xs (cardinality 1 3)) (multislot ys)
+                        (slot color (allowed-symbols red green blue)))
+        "#).unwrap();
+        let template = env.find_template("f1").unwrap();
+        assert_eq!(template.slot_cardinality("xs"), Some((1, Some(3))));
+        assert_eq!(template.slot_cardinality("ys"), Some((0, None)));
+        assert_eq!(template.slot_cardinality("color"), Some((1, Some(1))));
+        assert_eq!(template.slot_cardinality("zs"), None);
+        assert_eq!(template.slot_allowed_values("color"), Some(vec![
+            OwnedValue::Symbol(String::from("red")),
+            OwnedValue::Symbol(String::from("green")),
+            OwnedValue::Symbol(String::from("blue")),
+        ]));
+        assert_eq!(template.slot_allowed_values("xs"), None);
+        assert_eq!(template.slot_allowed_values("zs"), None);
+    }
+
+    #[test]
+    fn assert_retained() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        (deftemplate f2 (slot b))
+        (defrule retract-f1 ?f <- (f1) => (retract ?f))
+        (defrule churn ?f <- (f2 (b ?b&:(< ?b 100))) => (retract ?f) (assert (f2 (b (+ ?b 1)))))
+        "#).unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", "retained").unwrap();
+        let fact = fb.assert_retained().unwrap();
+        let fb = env.new_fact_builder("f2");
+        fb.put("b", 0).unwrap();
+        fb.assert().unwrap();
+        let index = fact.index();
+        env.run(None);
+        assert_eq!(env.number_of_facts(), 1);
+        // retracted, but still valid
+        assert_eq!(fact.index(), index);
+        assert_eq!(fact.template().name(), "f1");
+        assert_eq!(fact.accessor().get::<String, _>("a"), Some(String::from("retained")));
+        let copy = fact.clone();
+        drop(fact);
+        assert_eq!(copy.accessor().get::<String, _>("a"), Some(String::from("retained")));
+    }
+
+    #[test]
+    fn query() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a) (slot b))
+        "#).unwrap();
+        for a in 1..4 {
+            let fb = env.new_fact_builder("f1");
+            fb.put("a", a).unwrap();
+            fb.put("b", "b").unwrap();
+            fb.assert().unwrap();
+        }
+        let facts = env.query("f1").where_slot_eq("a", 2).collect();
+        assert_eq!(facts.len(), 1);
+        assert_eq!(i64::value(&facts[0].slot("a")), Some(2));
+        assert_eq!(env.query("f1").where_slot_eq("b", String::from("b")).collect().len(), 3);
+        assert_eq!(env.query("f1").where_slot_eq("a", 2).where_slot_eq("b", String::from("c")).collect().len(), 0);
+        assert_eq!(env.query("f1").where_slot_eq("c", 2).collect().len(), 0);
+        assert_eq!(env.query("f2").collect().len(), 0);
+    }
+
+    #[test]
+    fn is_template() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        (deftemplate f2 (slot b))
+        "#).unwrap();
+        for (i, &(template, slot)) in [("f1", "a"), ("f2", "b"), ("f1", "a")].iter().enumerate() {
+            let fb = env.new_fact_builder(template);
+            fb.put(slot, i).unwrap();
+            fb.assert().unwrap();
+        }
+        let (f1, f2): (Vec<_>, Vec<_>) = env.fact_iter().partition(|f| f.is_template("f1"));
+        assert_eq!(f1.len(), 2);
+        assert_eq!(f2.len(), 1);
+        assert!(f2[0].is_template("f2"));
+        assert!(!f2[0].is_template("f3"));
+    }
+
 }
@@ -1,20 +1,95 @@
 extern crate clips_sys as sys;
 #[macro_use] extern crate enum_primitive;
 #[macro_use] extern crate derive_error;
-#[cfg(test)] extern crate tempfile;
+extern crate tempfile;
+#[cfg(feature = "json")] extern crate serde;
+#[cfg(feature = "json")] extern crate serde_json;
 
 pub mod value;
-pub use value::{Type, Symbol, Value, ValueAccess};
+pub use value::{Type, Symbol, Value, ValueAccess, EnvAllocatable, OwnedValue, Number};
+pub use value::{escape_string, escape_symbol};
 
 pub mod fact;
-pub use fact::{Fact, FactBuilder, Template};
+pub use fact::{Fact, FactBuilder, FactQuery, Template, RetainedFact, SlotDef, DefaultKind};
+use fact::{Assertable, FactType};
+
+/// Declares an enum over facts of different types (implementing
+/// `fact::FactType`, such as ones derived with `clips_fact`), dispatched
+/// by template name when converted from a `Fact`:
+///
+/// ```ignore
+/// clips_fact_enum! {
+///     pub enum AnyFact {
+///         Person(Person),
+///         Order(Order),
+///     }
+/// }
+///
+/// match AnyFact::from(fact) {
+///     AnyFact::Person(person) => ...,
+///     AnyFact::Order(order) => ...,
+///     AnyFact::Other(fact) => ...,
+/// }
+/// ```
+///
+/// Facts of templates not listed are wrapped into `Other`
+#[macro_export]
+macro_rules! clips_fact_enum {
+    ($(#[$meta:meta])* $vis:vis enum $name:ident { $($variant:ident($ty:ty)),* $(,)* }) => {
+        $(#[$meta])*
+        $vis enum $name<'env> {
+            $($variant(<$ty as $crate::fact::FactType<'env>>::Asserted),)*
+            Other($crate::Fact<'env>),
+        }
+
+        impl<'env> ::std::convert::From<$crate::Fact<'env>> for $name<'env> {
+            fn from(fact: $crate::Fact<'env>) -> Self {
+                $(
+                if fact.is_template(<$ty as $crate::fact::FactType<'env>>::template_name()) {
+                    return $name::$variant(<$ty as $crate::fact::FactType<'env>>::wrap(fact));
+                }
+                )*
+                $name::Other(fact)
+            }
+        }
+    };
+}
+
+pub mod builder;
+pub use builder::Builder;
+
+pub mod class;
+pub use class::{Class, MessageHandler};
+
+pub mod udf;
 
 use std::ffi::CString;
 
+/// Releases process-global CLIPS state
+///
+/// CLIPS 6.40 keeps all of its state within environments, which
+/// release it when dropped, so this function does nothing. It is safe
+/// to call at any time, any number of times, and environments can
+/// still be created afterwards
+pub fn shutdown() {}
+
 /// CLIPS environment. Vast majority of APIs is only
 /// available through an environment
 pub struct Environment {
     pub(crate) env: *mut ::sys::environmentData,
+    pub(crate) state: ::std::rc::Rc<State>,
+    /// Handle to this environment passed to user-defined functions
+    /// (null for the handle itself, as it doesn't own the environment)
+    handle: *mut Environment,
+}
+
+/// State shared by an environment and its handle
+#[derive(Default)]
+pub(crate) struct State {
+    /// User-defined functions, by name
+    pub(crate) udfs: ::std::cell::RefCell<::std::collections::HashMap<String, udf::Function>>,
+    /// Values retained within `with_gc_disabled`
+    pub(crate) retained: ::std::cell::RefCell<Option<Vec<sys::CLIPSValue>>>,
 }
 
 use enum_primitive::FromPrimitive;
@@ -35,8 +110,28 @@ pub enum EvalError {
 }
 }
 
+/// Failure to load constructs (see `Environment::load_string_verbose`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LoadFailure {
+    /// Constructs failed to load. Contains everything printed
+    /// while loading, including error messages
+    Error(String),
+    /// Output is already being captured (for example, by an enclosing
+    /// `load_string_verbose`), so nothing was loaded
+    OutputCaptured,
+}
+
+/// Snapshot of the environment taken in between rule
+/// firings (see `Environment::run_with_sampler`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RunSample {
+    /// Number of rules fired so far
+    pub fired_so_far: usize,
+    /// Number of asserted facts
+    pub fact_count: usize,
+}
 
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 impl Environment {
 
@@ -46,25 +141,112 @@ impl Environment {
         if env == ::std::ptr::null_mut() {
             Err(())
         } else {
-            Ok(Environment {
+            let state = ::std::rc::Rc::new(State::default());
+            let handle = Box::into_raw(Box::new(Environment {
                 env,
-            })
+                state: state.clone(),
+                handle: ::std::ptr::null_mut(),
+            }));
+            Ok(Environment { env, state, handle })
+        }
+    }
+
+    /// Creates a new environment and loads constructs from
+    /// a binary image (as produced by the CLIPS `bsave` command)
+    ///
+    /// As CLIPS can only load binary images from files, the image is
+    /// written to a temporary file first
+    pub fn from_embedded_image(bytes: &[u8]) -> Result<Self, ()> {
+        use std::io::Write;
+        let env = Environment::new()?;
+        // the file is created exclusively, under a random name, and
+        // removed when dropped
+        let mut file = tempfile::NamedTempFile::new().map_err(|_| ())?;
+        file.write_all(bytes).and_then(|_| file.flush()).map_err(|_| ())?;
+        let c_string = CString::new(file.path().to_str().ok_or(())?).unwrap();
+        if unsafe { sys::Bload(env.env, c_string.as_ptr()) } {
+            Ok(env)
+        } else {
+            Err(())
         }
     }
 
     /// Allows an expression to be evaluated
+    ///
+    /// Note that CLIPS 6.40 has no auto-float-dividend setting:
+    /// `(/ 1 2)` always evaluates to a float (`0.5`), while integer
+    /// division is done with `div` (`(div 1 2)` evaluates to `0`)
     pub fn eval<S: AsRef<str>>(&self, expr: S) -> Result<Value, EvalError> {
-        let c_string = CString::new(expr.as_ref()).unwrap();
         let mut val : Value = unsafe { ::std::mem::zeroed() };
+        self.eval_into(expr, &mut val).and(Ok(val))
+    }
+
+    /// Allows an expression to be evaluated, storing the result
+    /// in an existing value (for example, one returned by
+    /// a previous evaluation)
+    ///
+    /// This is useful for evaluating many expressions in a loop
+    pub fn eval_into<S: AsRef<str>>(&self, expr: S, out: &mut Value) -> Result<(), EvalError> {
+        let c_string = CString::new(expr.as_ref()).unwrap();
         let return_code = unsafe {
-            sys::Eval(self.env, c_string.as_ptr(), &mut val.0)
+            sys::Eval(self.env, c_string.as_ptr(), &mut out.0)
         };
+        if let Some(ref mut retained) = *self.state.retained.borrow_mut() {
+            unsafe {
+                sys::RetainCV(self.env, &mut out.0);
+            }
+            retained.push(out.0);
+        }
         match return_code {
-            sys::EvalError::EE_NO_ERROR => Ok(val),
+            sys::EvalError::EE_NO_ERROR => Ok(()),
             err => Err(EvalError::from_isize(err as isize).expect("valid return code")),
         }
     }
 
+    /// Returns `true` if the evaluation error flag is set
+    ///
+    /// The flag is set when an error occurs during evaluation
+    /// (for example, a failed `eval`) and stays set until cleared
+    pub fn evaluation_error(&self) -> bool {
+        unsafe {
+            sys::GetEvaluationError(self.env)
+        }
+    }
+
+    /// Clears the evaluation error flag
+    pub fn clear_evaluation_error(&self) {
+        unsafe {
+            sys::SetEvaluationError(self.env, false)
+        }
+    }
+
+    /// Runs `f` with garbage collection of values returned by `eval`
+    /// (and `eval_into`) suspended, so that they all remain valid
+    /// throughout `f`. These values are released once `f` returns,
+    /// even if it panics
+    pub fn with_gc_disabled<R, F: FnOnce(&Environment) -> R>(&self, f: F) -> R {
+        struct Guard<'a>(&'a Environment);
+
+        impl<'a> Drop for Guard<'a> {
+            fn drop(&mut self) {
+                let retained = self.0.state.retained.borrow_mut().take().unwrap_or_default();
+                for mut value in retained {
+                    unsafe {
+                        sys::ReleaseCV((self.0).env, &mut value);
+                    }
+                }
+            }
+        }
+
+        // nested blocks are covered by the outermost one
+        if self.state.retained.borrow().is_some() {
+            return f(self);
+        }
+        *self.state.retained.borrow_mut() = Some(vec![]);
+        let _guard = Guard(self);
+        f(self)
+    }
+
     /// Loads a set of constructs into the CLIPS data base (the equivalent
     /// of the CLIPS load command).
     pub fn load<P: AsRef<Path>>(&self, file: P) -> Result<(), LoadError> {
@@ -78,6 +260,15 @@ impl Environment {
         }
     }
 
+    /// Loads constructs from multiple files, in order. Stops at the first
+    /// file that fails to load, returning its path along with the error
+    pub fn load_all<P: AsRef<Path>, I: IntoIterator<Item = P>>(&self, files: I) -> Result<(), (PathBuf, LoadError)> {
+        for file in files {
+            self.load(&file).map_err(|err| (file.as_ref().to_path_buf(), err))?;
+        }
+        Ok(())
+    }
+
     /// Loads a set of constructs into the CLIPS database from a memory-based
     /// source (as opposed to an existing file)
     pub fn load_string<S: AsRef<str>>(&self, str: S) -> Result<(), ()> {
@@ -92,11 +283,191 @@ impl Environment {
         }
     }
 
+    /// Loads a set of constructs from a string (see `load_string`),
+    /// returning the compilation trace (as printed when compilations
+    /// are watched)
+    pub fn load_string_verbose<S: AsRef<str>>(&self, str: S) -> Result<String, LoadFailure> {
+        let (watching, printing) = unsafe {
+            (sys::GetWatchState(self.env, sys::WatchItem::COMPILATIONS), sys::GetPrintWhileLoading(self.env))
+        };
+        unsafe {
+            sys::SetWatchState(self.env, sys::WatchItem::COMPILATIONS, true);
+            sys::SetPrintWhileLoading(self.env, true);
+        }
+        let captured = self.capture_output_of(&["stdout", "stderr", "stdwrn"], || self.load_string(str));
+        unsafe {
+            sys::SetWatchState(self.env, sys::WatchItem::COMPILATIONS, watching);
+            sys::SetPrintWhileLoading(self.env, printing);
+        }
+        match captured {
+            Ok((Ok(()), output)) => Ok(output),
+            Ok((Err(()), output)) => Err(LoadFailure::Error(output)),
+            Err(()) => Err(LoadFailure::OutputCaptured),
+        }
+    }
+
+    /// Defines a function (deffunction) with the given
+    /// parameters (such as `?a` or `$?rest`) and body
+    pub fn define_deffunction<S: AsRef<str>, B: AsRef<str>>(&self, name: S, params: &[&str], body: B) -> Result<(), LoadError> {
+        self.load_string(format!("(deffunction {} ({}) {})", name.as_ref(), params.join(" "), body.as_ref()))
+            .or(Err(LoadError::ParsingError))
+    }
+
+    /// Defines a template (deftemplate) with the given slots
+    pub fn define_template<S: AsRef<str>>(&self, name: S, slots: &[SlotDef]) -> Result<(), LoadError> {
+        let slots: Vec<String> = slots.iter().map(SlotDef::to_string).collect();
+        self.load_string(format!("(deftemplate {} {})", name.as_ref(), slots.join(" ")))
+            .or(Err(LoadError::ParsingError))
+    }
+
+    /// Defines a message handler (defmessage-handler) for a class
+    /// with the given parameters and body
+    pub fn define_message_handler<C, S, B>(&self, class: C, name: S, params: &[&str], body: B) -> Result<(), LoadError>
+        where C: AsRef<str>, S: AsRef<str>, B: AsRef<str> {
+        self.load_string(format!("(defmessage-handler {} {} ({}) {})",
+                                 class.as_ref(), name.as_ref(), params.join(" "), body.as_ref()))
+            .or(Err(LoadError::ParsingError))
+    }
+
+    /// Finds a class (if there's one by the given name)
+    pub fn find_class<S: AsRef<str>>(&self, class: S) -> Option<Class> {
+        let c_string = CString::new(class.as_ref()).unwrap();
+        let defclass = unsafe {
+            sys::FindDefclass(self.env, c_string.as_ptr())
+        };
+        if defclass.is_null() {
+            None
+        } else {
+            Some(Class { class: defclass, env: ::std::marker::PhantomData })
+        }
+    }
+
+    /// Saves all instances of the current module into a file (the equivalent
+    /// of the CLIPS `save-instances` command), returning the number
+    /// of instances saved
+    pub fn save_instances<P: AsRef<Path>>(&self, path: P) -> Result<usize, ()> {
+        let c_string = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+        let count = unsafe {
+            sys::SaveInstances(self.env, c_string.as_ptr(), sys::SaveScope::LOCAL_SAVE)
+        };
+        if count < 0 || self.evaluation_error() {
+            Err(())
+        } else {
+            Ok(count as usize)
+        }
+    }
+
+    /// Loads instances from a file (the equivalent of the CLIPS `load-instances`
+    /// command), returning the number of instances loaded
+    pub fn load_instances<P: AsRef<Path>>(&self, path: P) -> Result<usize, ()> {
+        let c_string = CString::new(path.as_ref().to_str().unwrap()).unwrap();
+        let count = unsafe {
+            sys::LoadInstances(self.env, c_string.as_ptr())
+        };
+        if count < 0 {
+            Err(())
+        } else {
+            Ok(count as usize)
+        }
+    }
+
+    /// Defines a global variable (defglobal) with the given value.
+    /// The name is given without `?*` and `*` (`threshold` for `?*threshold*`)
+    ///
+    /// Values that can't be written in CLIPS syntax (fact addresses)
+    /// are rejected
+    pub fn define_global<S: AsRef<str>, V: EnvAllocatable>(&self, name: S, value: V) -> Result<(), ()> {
+        use value::ClipsDisplay;
+
+        // the value as written in the defglobal construct
+        struct Initial(OwnedValue);
+
+        impl ::std::fmt::Display for Initial {
+            fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                match self.0 {
+                    OwnedValue::Multifield(_) => {
+                        f.write_str("(create$ ")?;
+                        self.0.fmt_clips(f)?;
+                        f.write_str(")")
+                    },
+                    _ => self.0.fmt_clips(f),
+                }
+            }
+        }
+
+        let value = match OwnedValue::value(&value.allocate(self)) {
+            None | Some(OwnedValue::FactAddress(_)) | Some(OwnedValue::Void) => return Err(()),
+            Some(value) => value,
+        };
+        self.load_string(format!("(defglobal ?*{}* = {})", name.as_ref(), Initial(value)))
+    }
+
+    /// Returns the value of a global variable (if there's one by the given name).
+    /// The name is given without `?*` and `*`
+    pub fn defglobal_value<S: AsRef<str>>(&self, name: S) -> Option<Value> {
+        let c_string = CString::new(name.as_ref()).unwrap();
+        let defglobal = unsafe {
+            sys::FindDefglobal(self.env, c_string.as_ptr())
+        };
+        if defglobal.is_null() {
+            None
+        } else {
+            let mut val : Value = unsafe { ::std::mem::zeroed() };
+            unsafe {
+                sys::DefglobalGetValue(defglobal, &mut val.0)
+            }
+            Some(val)
+        }
+    }
+
     /// The main entry point for building new facts. Takes template name
     pub fn new_fact_builder<S: AsRef<str>>(&self, template: S) -> FactBuilder {
         FactBuilder::new(self, template)
     }
 
+    /// Asserts all items in order, stopping at the first failure.
+    /// On failure, returns the index of the failing item alongside
+    /// the error.
+    ///
+    /// If `atomic` is `true`, facts asserted before the failure
+    /// are retracted
+    pub fn try_assert_all<'env, T, I>(&'env self, items: I, atomic: bool) -> Result<Vec<T::T>, (usize, T::Error)>
+        where T: Assertable<'env>, I: IntoIterator<Item = T> {
+        let mut asserted = vec![];
+        for (index, item) in items.into_iter().enumerate() {
+            match item.assert(self) {
+                Ok(fact) => asserted.push(fact),
+                Err(err) => {
+                    if atomic {
+                        for fact in asserted {
+                            let _ = (*fact).clone().retract();
+                        }
+                    }
+                    return Err((index, err));
+                }
+            }
+        }
+        Ok(asserted)
+    }
+
+    /// Asserts a fact unless a fact with the same slot values
+    /// already exists, in which case the existing fact is returned.
+    ///
+    /// Fact duplication is disabled for the duration of the assertion
+    /// (and restored afterwards), so asserting an existing fact has
+    /// no side effects. Note that this includes any rules run as part of
+    /// the assertion (such as with `run_on_assert` facts), so facts
+    /// asserted by their actions won't be duplicated either
+    pub fn assert_unique<'env, T>(&'env self, fact: T) -> Result<T::T, T::Error>
+        where T: Assertable<'env> {
+        let duplication = unsafe { sys::SetFactDuplication(self.env, false) };
+        let result = fact.assert(self);
+        unsafe {
+            sys::SetFactDuplication(self.env, duplication);
+        }
+        result
+    }
+
     /// Returns the number of asserted facts
     pub fn number_of_facts(&self) -> usize {
         unsafe {
@@ -104,6 +475,18 @@ impl Environment {
         }
     }
 
+    /// Retracts all facts, returning the number of retracted facts.
+    ///
+    /// Unlike `(reset)`, doesn't assert deffacts and unlike `(clear)`,
+    /// keeps all constructs defined
+    pub fn retract_all_facts(&self) -> usize {
+        let count = self.number_of_facts();
+        unsafe {
+            sys::RetractAllFacts(self.env);
+        }
+        count.saturating_sub(self.number_of_facts())
+    }
+
     /// Returns an iterator over all asserted facts
     pub fn fact_iter(&self) -> fact::Iter {
         fact::Iter::new(self)
@@ -122,6 +505,188 @@ impl Environment {
         }
     }
 
+    /// Returns the number of activations on the agenda
+    pub fn number_of_activations(&self) -> usize {
+        unsafe {
+            sys::GetNumberOfActivations(self.env) as usize
+        }
+    }
+
+    /// Captures everything written to a temporary logical name
+    /// (passed to `f`) into a string
+    pub(crate) fn capture_output<F: FnOnce(&str)>(&self, f: F) -> String {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("clips-rs-capture-{}", COUNTER.fetch_add(1, Ordering::SeqCst));
+        self.capture_output_of(&[&name], || f(&name))
+            .map(|((), output)| output)
+            .expect("unique logical name")
+    }
+
+    /// Captures everything written to given logical names while `f`
+    /// runs into a string. Fails if any of these names is already captured
+    pub(crate) fn capture_output_of<R, F: FnOnce() -> R>(&self, names: &[&str], f: F) -> Result<(R, String), ()> {
+        // closes destinations and disposes of the string builder
+        // even if `f` panics
+        struct Guard<'a> {
+            env: &'a Environment,
+            sb: *mut sys::StringBuilder,
+            names: Vec<CString>,
+        }
+
+        impl<'a> Drop for Guard<'a> {
+            fn drop(&mut self) {
+                unsafe {
+                    for name in self.names.iter() {
+                        sys::CloseStringBuilderDestination(self.env.env, name.as_ptr());
+                    }
+                    sys::SBDispose(self.sb);
+                }
+            }
+        }
+
+        let mut guard = Guard {
+            env: self,
+            sb: unsafe { sys::CreateStringBuilder(self.env, 0) },
+            names: vec![],
+        };
+        for name in names {
+            let c_string = CString::new(*name).unwrap();
+            if !unsafe { sys::OpenStringBuilderDestination(self.env, c_string.as_ptr(), guard.sb) } {
+                return Err(());
+            }
+            guard.names.push(c_string);
+        }
+        let result = f();
+        let output = unsafe {
+            if (*guard.sb).contents.is_null() {
+                String::new()
+            } else {
+                ::std::ffi::CStr::from_ptr((*guard.sb).contents).to_string_lossy().into_owned()
+            }
+        };
+        Ok((result, output))
+    }
+
+    /// Returns the name of the module on top of the focus stack
+    pub fn get_focus(&self) -> Option<String> {
+        let module = unsafe { sys::GetFocus(self.env) };
+        if module.is_null() {
+            None
+        } else {
+            let name = unsafe { ::std::ffi::CStr::from_ptr(sys::DefmoduleName(module)) };
+            Some(String::from(name.to_str().unwrap()))
+        }
+    }
+
+    /// Removes the module on top of the focus stack, returning its name
+    pub fn pop_focus(&self) -> Option<String> {
+        let module = unsafe { sys::PopFocus(self.env) };
+        if module.is_null() {
+            None
+        } else {
+            let name = unsafe { ::std::ffi::CStr::from_ptr(sys::DefmoduleName(module)) };
+            Some(String::from(name.to_str().unwrap()))
+        }
+    }
+
+    /// Places all current activations of a rule (defrule) on the agenda
+    /// again, including the ones that have already fired (the equivalent
+    /// of the CLIPS `refresh` command)
+    pub fn refresh_rule<S: AsRef<str>>(&self, name: S) -> Result<(), ()> {
+        let c_string = CString::new(name.as_ref()).unwrap();
+        let defrule = unsafe {
+            sys::FindDefrule(self.env, c_string.as_ptr())
+        };
+        if defrule.is_null() {
+            Err(())
+        } else {
+            unsafe {
+                sys::Refresh(defrule)
+            }
+            Ok(())
+        }
+    }
+
+    /// Changes the salience of a rule (defrule) and reorders
+    /// activations already on the agenda accordingly
+    ///
+    /// Salience has to be within the range of -10000 to 10000. Rules with
+    /// salience declared as an expression (dynamic salience) are rejected,
+    /// as the expression would override the new salience when re-evaluated
+    pub fn set_rule_salience<S: AsRef<str>>(&self, name: S, salience: i32) -> Result<(), ()> {
+        if !(-10000..=10000).contains(&salience) {
+            return Err(());
+        }
+        let c_string = CString::new(name.as_ref()).unwrap();
+        let defrule = unsafe {
+            sys::FindDefrule(self.env, c_string.as_ptr())
+        };
+        if defrule.is_null() || unsafe { !(*defrule).dynamicSalience.is_null() } {
+            return Err(());
+        }
+        let mut disjunct = defrule;
+        while !disjunct.is_null() {
+            unsafe {
+                (*disjunct).salience = salience;
+                disjunct = (*disjunct).disjunct;
+            }
+        }
+        unsafe {
+            sys::RefreshAllAgendas(self.env)
+        }
+        Ok(())
+    }
+
+    /// Returns `true` if the agenda has changed since
+    /// the flag was last cleared
+    pub fn agenda_changed(&self) -> bool {
+        unsafe {
+            sys::GetAgendaChanged(self.env)
+        }
+    }
+
+    /// Clears the agenda change flag
+    pub fn clear_agenda_changed(&self) {
+        unsafe {
+            sys::SetAgendaChanged(self.env, false)
+        }
+    }
+
+    /// Returns all facts with the given template, or none
+    /// if there's no such template
+    pub fn facts_with_template<S: AsRef<str>>(&self, name: S) -> Vec<Fact> {
+        match self.find_template(name) {
+            Some(template) => template.into_fact_iter().collect(),
+            None => vec![],
+        }
+    }
+
+    /// Starts a query over facts with the given template
+    pub fn query<S: AsRef<str>>(&self, template: S) -> FactQuery {
+        FactQuery::new(self, template)
+    }
+
+    /// Returns all facts with the given template satisfying a query
+    /// (the equivalent of the CLIPS `find-all-facts` function). The fact is
+    /// bound to `?f` in the query, for example, `(> ?f:age 30)`
+    ///
+    /// Returns no facts if the query fails to evaluate
+    pub fn find_all_facts<T: AsRef<str>, Q: AsRef<str>>(&self, template: T, query: Q) -> Vec<Fact> {
+        self.eval(format!("(find-all-facts ((?f {})) {})", template.as_ref(), query.as_ref()))
+            .ok()
+            .and_then(|val| val.as_facts(self))
+            .unwrap_or_default()
+    }
+
+    /// Returns all facts of the given type
+    pub fn facts_of<'env, T: FactType<'env>>(&'env self) -> Vec<T::Asserted> {
+        match self.find_template(T::template_name()) {
+            Some(template) => template.into_fact_iter().map(T::wrap).collect(),
+            None => vec![],
+        }
+    }
+
     /// Starts execution of rules.
     /// It is the equivalent of the CLIPS `run` command
     ///
@@ -138,11 +703,123 @@ impl Environment {
         }
     }
 
+    /// Starts execution of rules (see `run`), taking a sample
+    /// after every `every` rule firings (and once execution ceases).
+    ///
+    /// Returns the total number of rules fired.
+    ///
+    /// Panics if `every` is zero
+    pub fn run_with_sampler<F: FnMut(RunSample)>(&self, limit: Option<usize>, every: usize, mut f: F) -> usize {
+        assert!(every > 0, "sampling interval should be positive");
+        let mut fired_so_far = 0;
+        loop {
+            let batch = match limit {
+                None => every,
+                Some(n) => ::std::cmp::min(every, n - fired_so_far),
+            };
+            let fired = self.run(Some(batch));
+            fired_so_far += fired;
+            f(RunSample { fired_so_far, fact_count: self.number_of_facts() });
+            if fired < batch || limit == Some(fired_so_far) {
+                return fired_so_far;
+            }
+        }
+    }
+
+}
+
+/// Error asserting a fact from JSON (see `Environment::assert_json`)
+#[cfg(feature = "json")]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Error)]
+pub enum JsonAssertError {
+    /// JSON can't be parsed
+    InvalidJson,
+    /// JSON is not an object
+    NotAnObject,
+    /// Slot value is an object or a nested array
+    UnsupportedValue,
+    /// Slot doesn't exist or can't hold the value
+    InvalidSlot,
+    /// Fact can't be asserted
+    AssertionFailed,
+}
+
+#[cfg(feature = "json")]
+struct Json<'a>(&'a serde_json::Value);
+
+#[cfg(feature = "json")]
+impl<'a> Json<'a> {
+    fn is_supported(&self, nested: bool) -> bool {
+        match *self.0 {
+            serde_json::Value::Object(_) => false,
+            serde_json::Value::Array(ref values) => !nested && values.iter().all(|v| Json(v).is_supported(true)),
+            _ => true,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl<'a> EnvAllocatable for Json<'a> {
+    fn allocate(&self, env: &Environment) -> Value {
+        match *self.0 {
+            serde_json::Value::Null => Symbol("nil").allocate(env),
+            serde_json::Value::Bool(b) => b.allocate(env),
+            serde_json::Value::Number(ref n) => match n.as_i64() {
+                Some(i) => i.allocate(env),
+                None => n.as_f64().unwrap().allocate(env),
+            },
+            serde_json::Value::String(ref s) => s.allocate(env),
+            serde_json::Value::Array(ref values) => values.iter().map(Json).collect::<Vec<_>>().allocate(env),
+            serde_json::Value::Object(_) => panic!("objects can't be allocated"),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl Environment {
+    /// Asserts a fact with the given template from a JSON object,
+    /// such as `{"a":1,"b":"x"}`, mapping its fields onto slots
+    ///
+    /// JSON strings become CLIPS strings, `null` becomes `nil`
+    /// and arrays become multifields
+    pub fn assert_json<T: AsRef<str>, J: AsRef<str>>(&self, template: T, json: J) -> Result<Fact, JsonAssertError> {
+        let json: serde_json::Value = serde_json::from_str(json.as_ref())
+            .or(Err(JsonAssertError::InvalidJson))?;
+        let slots = match json {
+            serde_json::Value::Object(slots) => slots,
+            _ => return Err(JsonAssertError::NotAnObject),
+        };
+        if !slots.values().all(|v| Json(v).is_supported(false)) {
+            return Err(JsonAssertError::UnsupportedValue);
+        }
+        let fb = self.new_fact_builder(template);
+        for (slot, value) in slots.iter() {
+            fb.put(slot, Json(value)).or(Err(JsonAssertError::InvalidSlot))?;
+        }
+        fb.assert().or(Err(JsonAssertError::AssertionFailed))
+    }
+}
+
+impl Environment {
+    /// Handle to this environment passed to user-defined functions
+    pub(crate) fn handle(&self) -> *const Environment {
+        if self.handle.is_null() {
+            self
+        } else {
+            self.handle
+        }
+    }
 }
 
 impl Drop for Environment {
     fn drop(&mut self) {
-        unsafe { sys::DestroyEnvironment(self.env); }
+        // handles don't own the environment
+        if !self.handle.is_null() {
+            unsafe {
+                sys::DestroyEnvironment(self.env);
+                drop(Box::from_raw(self.handle));
+            }
+        }
     }
 }
 
@@ -151,14 +828,83 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn shutdown() {
+        for _ in 0..3 {
+            let env = Environment::new().unwrap();
+            env.load_string("(deftemplate tpl1)").unwrap();
+        }
+        super::shutdown();
+        super::shutdown();
+        assert!(Environment::new().unwrap().eval("(+ 1 2)").is_ok());
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn assert_json() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a) (slot b) (multislot c))").unwrap();
+        let fact = env.assert_json("f1", r#"{"a":1,"b":"x","c":[1.5,null,true]}"#).unwrap();
+        assert_eq!(i64::value(&fact.slot("a")), Some(1));
+        assert_eq!(String::value(&fact.slot("b")), Some(String::from("x")));
+        assert_eq!(OwnedValue::value(&fact.slot("c")), Some(OwnedValue::Multifield(vec![
+            OwnedValue::Float(1.5),
+            OwnedValue::Symbol(String::from("nil")),
+            OwnedValue::Symbol(String::from("TRUE")),
+        ])));
+        assert_eq!(env.assert_json("f1", "{").err(), Some(JsonAssertError::InvalidJson));
+        assert_eq!(env.assert_json("f1", "[1]").err(), Some(JsonAssertError::NotAnObject));
+        assert_eq!(env.assert_json("f1", r#"{"c":[[1]]}"#).err(), Some(JsonAssertError::UnsupportedValue));
+        assert_eq!(env.assert_json("f1", r#"{"a":{}}"#).err(), Some(JsonAssertError::UnsupportedValue));
+        assert_eq!(env.assert_json("f1", r#"{"d":1}"#).err(), Some(JsonAssertError::InvalidSlot));
+        assert_eq!(env.assert_json("f1", r#"{"a":[1]}"#).err(), Some(JsonAssertError::InvalidSlot));
+    }
+
     #[test]
     fn sanity_check() {
         assert_eq!(Environment::new().unwrap().eval("\"a\"").unwrap().type_of(),
                    Type::String);
     }
 
+    #[test]
+    fn eval_into() {
+        let env = Environment::new().unwrap();
+        let mut val = env.eval("0").unwrap();
+        for i in 1..5 {
+            env.eval_into(format!("(* {} 2)", i), &mut val).unwrap();
+            assert_eq!(i64::value(&val), Some(i * 2));
+        }
+        env.eval_into("\"a\"", &mut val).unwrap();
+        assert_eq!(String::value(&val), Some(String::from("a")));
+        assert_eq!(env.eval_into("(+ 1", &mut val).err(), Some(EvalError::ParsingError));
+    }
+
+    #[test]
+    fn evaluation_error() {
+        let env = Environment::new().unwrap();
+        assert!(!env.evaluation_error());
+        assert_eq!(env.eval("(div 1 0)").err(), Some(EvalError::ProcessingError));
+        assert!(env.evaluation_error());
+        env.clear_evaluation_error();
+        assert!(!env.evaluation_error());
+        assert_eq!(env.eval("(+ 1 2)").unwrap().type_of(), Type::Integer);
+    }
+
+    // CLIPS 6.40 has no auto-float-dividend setting: `/` always
+    // produces a float and `div` always produces an integer
+    #[test]
+    fn division() {
+        let env = Environment::new().unwrap();
+        let val = env.eval("(/ 1 2)").unwrap();
+        assert_eq!(val.type_of(), Type::Float);
+        assert_eq!(f64::value(&val), Some(0.5));
+        let val = env.eval("(div 1 2)").unwrap();
+        assert_eq!(val.type_of(), Type::Integer);
+        assert_eq!(i64::value(&val), Some(0));
+    }
+
     use tempfile;
-    use std::io::Write;
+    use std::io::{Read, Write};
 
     #[test]
     fn load() {
@@ -175,6 +921,152 @@ mod tests {
         assert_eq!(env.eval("(test)").unwrap().type_of(), Type::Integer);
     }
 
+    #[test]
+    fn load_all() {
+        let env = Environment::new().unwrap();
+        let mut template = tempfile::NamedTempFile::new().unwrap();
+        template.write_all(b"(deftemplate tpl1 (slot a))").unwrap();
+        let mut rule = tempfile::NamedTempFile::new().unwrap();
+        rule.write_all(b"(defrule rule1 (tpl1 (a 1)) =>)").unwrap();
+
+        env.load_all([template.path(), rule.path()]).unwrap();
+
+        assert!(env.find_template("tpl1").is_some());
+        assert!(env.refresh_rule("rule1").is_ok());
+        assert_eq!(env.load_all(vec![Path::new("no_such_file"), template.path()]),
+                   Err((PathBuf::from("no_such_file"), LoadError::OpenFileError)));
+    }
+
+    #[test]
+    fn save_load_instances() {
+        let env = Environment::new().unwrap();
+        env.load_string("(defclass C (is-a USER) (slot x))").unwrap();
+        env.eval("(make-instance a of C (x 1))").unwrap();
+        env.eval("(make-instance b of C (x \"b\"))").unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        assert_eq!(env.save_instances(file.path()), Ok(2));
+        env.eval("(unmake-instance *)").unwrap();
+        assert_eq!(env.eval("(instance-existp [a])").unwrap().as_bool_with(&["TRUE"], &["FALSE"]), Some(false));
+        assert_eq!(env.load_instances(file.path()), Ok(2));
+        assert_eq!(env.eval("(send [a] get-x)").unwrap().as_number::<i64>(), Some(1));
+        assert_eq!(String::value(&env.eval("(send [b] get-x)").unwrap()), Some(String::from("b")));
+        assert!(env.load_instances("no_such_file").is_err());
+        assert!(env.save_instances("no_such_dir/file").is_err());
+    }
+
+    #[test]
+    fn from_embedded_image() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deffunction test () 1)").unwrap();
+        let file = tempfile::NamedTempFile::new().unwrap();
+        env.eval(format!("(bsave {})", escape_string(file.path().to_str().unwrap()))).unwrap();
+        let mut image = vec![];
+        ::std::fs::File::open(file.path()).unwrap().read_to_end(&mut image).unwrap();
+
+        let env = Environment::from_embedded_image(&image).unwrap();
+        assert_eq!(env.eval("(test)").unwrap().as_number::<i64>(), Some(1));
+        assert!(Environment::from_embedded_image(b"not an image").is_err());
+    }
+
+    #[test]
+    fn define_template() {
+        let env = Environment::new().unwrap();
+        env.define_template("tpl1", &[
+            SlotDef::slot("a").default(DefaultKind::Static(OwnedValue::String(String::from("a")))),
+            SlotDef::multislot("b").default(DefaultKind::Static(OwnedValue::Multifield(vec![
+                OwnedValue::Integer(1), OwnedValue::Symbol(String::from("b"))]))),
+            SlotDef::slot("id").default(DefaultKind::Dynamic(String::from("(gensym*)"))),
+            SlotDef::slot("c"),
+        ]).unwrap();
+        let fact1 = env.new_fact_builder("tpl1").assert().unwrap();
+        let fact2 = env.new_fact_builder("tpl1").assert().unwrap();
+        assert_eq!(fact1.slot_map()[..2].to_vec(), vec![
+            (String::from("a"), OwnedValue::String(String::from("a"))),
+            (String::from("b"), OwnedValue::Multifield(vec![OwnedValue::Integer(1),
+                                                           OwnedValue::Symbol(String::from("b"))])),
+        ]);
+        assert_eq!(OwnedValue::value(&fact1.slot("c")), Some(OwnedValue::Symbol(String::from("nil"))));
+        let (id1, id2) = (OwnedValue::value(&fact1.slot("id")).unwrap(),
+                          OwnedValue::value(&fact2.slot("id")).unwrap());
+        assert_ne!(id1, id2);
+        assert!(env.define_template("tpl2", &[SlotDef::slot("a").default(DefaultKind::Dynamic(String::from("(")))]).is_err());
+    }
+
+    #[test]
+    fn with_gc_disabled() {
+        let env = Environment::new().unwrap();
+        let values = env.with_gc_disabled(|env| {
+            let values: Vec<_> = (0..10).map(|i| env.eval(format!("(create$ \"s{}\" {}.5)", i, i)).unwrap()).collect();
+            values.iter().map(|v| OwnedValue::value(v).unwrap()).collect::<Vec<_>>()
+        });
+        assert_eq!(values[3], OwnedValue::Multifield(vec![OwnedValue::String(String::from("s3")),
+                                                          OwnedValue::Float(3.5)]));
+        assert!(env.eval("(div 1 0)").is_err());
+        assert!(env.eval("(+ 1 2)").is_ok());
+    }
+
+    #[test]
+    fn with_gc_disabled_error() {
+        let env = Environment::new().unwrap();
+        env.with_gc_disabled(|env| {
+            let value = env.eval("(create$ a b)").unwrap();
+            assert!(env.eval("(div 1 0)").is_err());
+            assert_eq!(env.eval("(+ 1 2)").unwrap().as_number::<i64>(), Some(3));
+            let nested = env.with_gc_disabled(|env| env.eval("(str-cat x y)").unwrap());
+            assert_eq!(OwnedValue::value(&value).unwrap(),
+                       OwnedValue::Multifield(vec![OwnedValue::Symbol(String::from("a")),
+                                                   OwnedValue::Symbol(String::from("b"))]));
+            assert_eq!(OwnedValue::value(&nested).unwrap(), OwnedValue::String(String::from("xy")));
+        });
+    }
+
+    #[test]
+    fn with_gc_disabled_panic() {
+        let env = Environment::new().unwrap();
+        let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| {
+            env.with_gc_disabled(|env| {
+                env.eval("(div 1 0)").ok();
+                panic!("in block")
+            })
+        }));
+        assert!(result.is_err());
+        assert!(env.state.retained.borrow().is_none());
+        assert!(env.eval("(+ 1 2)").is_ok());
+    }
+
+    #[test]
+    fn load_string_verbose() {
+        let env = Environment::new().unwrap();
+        let trace = env.load_string_verbose(r#"
+        (deftemplate tpl1 (slot a))
+        (defrule rule1 (tpl1 (a 1)) =>)
+        "#).unwrap();
+        assert!(trace.contains("Defining deftemplate: tpl1"));
+        assert!(trace.contains("Defining defrule: rule1"));
+        let output = match env.load_string_verbose("(deftemplate tpl2) (defrule rule2 (") {
+            Err(LoadFailure::Error(output)) => output,
+            other => panic!("unexpected result {:?}", other),
+        };
+        assert!(output.contains("Defining deftemplate: tpl2"));
+        assert!(output.contains("ERROR"));
+        assert_eq!(env.load_string_verbose("(deftemplate tpl3)").unwrap().matches("Defining").count(), 1);
+    }
+
+    #[test]
+    fn capture_output_of() {
+        let env = Environment::new().unwrap();
+        let ((), output) = env.capture_output_of(&["stdout", "stderr"], || {
+            env.eval("(printout t \"out\" crlf)").unwrap();
+            env.eval("(printout stderr \"err\")").unwrap();
+            // nested capture of the same names fails
+            assert!(env.capture_output_of(&["stderr"], || ()).is_err());
+            assert_eq!(env.load_string_verbose("(deftemplate tpl1)"), Err(LoadFailure::OutputCaptured));
+        }).unwrap();
+        assert_eq!(output, "out\nerr");
+        assert!(env.find_template("tpl1").is_none());
+        assert_eq!(env.capture_output_of(&["stdout"], || ()).unwrap().1, "");
+    }
+
     #[test]
     fn load_file_error() {
         let env = Environment::new().unwrap();
@@ -205,6 +1097,35 @@ mod tests {
         assert_eq!(env.eval("(test)").unwrap().type_of(), Type::Integer);
     }
 
+    #[test]
+    fn define_deffunction() {
+        let env = Environment::new().unwrap();
+        env.define_deffunction("avg", &["?a", "?b"], "(/ (+ ?a ?b) 2)").unwrap();
+        assert_eq!(env.eval("(avg 2 4)").unwrap().as_number::<f64>(), Some(3.0));
+        assert_eq!(env.define_deffunction("broken", &["?a"], "(+ ?a").unwrap_err(), LoadError::ParsingError);
+    }
+
+    #[test]
+    fn define_global() {
+        let env = Environment::new().unwrap();
+        env.define_global("threshold", 3.5f64).unwrap();
+        assert_eq!(f64::value(&env.defglobal_value("threshold").unwrap()), Some(3.5));
+        assert_eq!(f64::value(&env.eval("?*threshold*").unwrap()), Some(3.5));
+        assert!(env.defglobal_value("no_such_global").is_none());
+        env.define_global("names", vec!["a \"b\"", "c"]).unwrap();
+        env.eval("(bind ?*threshold* 1.0)").unwrap();
+        env.eval("(bind ?*names* (create$))").unwrap();
+        // reset brings the given values back
+        env.eval("(reset)").unwrap();
+        assert_eq!(f64::value(&env.defglobal_value("threshold").unwrap()), Some(3.5));
+        assert_eq!(OwnedValue::value(&env.defglobal_value("names").unwrap()),
+                   Some(OwnedValue::Multifield(vec![OwnedValue::String(String::from("a \"b\"")),
+                                                    OwnedValue::String(String::from("c"))])));
+        env.load_string("(deftemplate tpl)").unwrap();
+        let fact = env.new_fact_builder("tpl").assert().unwrap();
+        assert!(env.define_global("fact", fact).is_err());
+    }
+
     #[test]
     fn run_empty() {
         let env = Environment::new().unwrap();
@@ -235,6 +1156,299 @@ mod tests {
         assert_eq!(env.run(Some(0)), 0);
     }
 
+    #[test]
+    fn run_with_sampler() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate counter (slot n))
+        (defrule step (counter (n ?n&:(< ?n 10))) => (assert (counter (n (+ ?n 1)))))
+        (deffacts start (counter (n 0)))
+        "#).unwrap();
+        env.eval("(reset)").unwrap();
+        let mut samples = vec![];
+        assert_eq!(env.run_with_sampler(None, 3, |sample| samples.push(sample)), 10);
+        assert_eq!(samples.iter().map(|s| s.fired_so_far).collect::<Vec<_>>(), vec![3, 6, 9, 10]);
+        assert_eq!(samples.iter().map(|s| s.fact_count).collect::<Vec<_>>(), vec![4, 7, 10, 11]);
+    }
+
+    #[test]
+    fn run_with_sampler_limit() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate counter (slot n))
+        (defrule step (counter (n ?n&:(< ?n 10))) => (assert (counter (n (+ ?n 1)))))
+        (deffacts start (counter (n 0)))
+        "#).unwrap();
+        env.eval("(reset)").unwrap();
+        let mut samples = vec![];
+        assert_eq!(env.run_with_sampler(Some(5), 2, |sample| samples.push(sample.fired_so_far)), 5);
+        assert_eq!(samples, vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn refresh_rule() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate tpl1)
+        (defrule rule1 (tpl1) => )
+        "#).unwrap();
+        env.new_fact_builder("tpl1").assert().unwrap();
+        assert_eq!(env.number_of_activations(), 1);
+        assert_eq!(env.run(None), 1);
+        assert_eq!(env.number_of_activations(), 0);
+        env.refresh_rule("rule1").unwrap();
+        assert_eq!(env.number_of_activations(), 1);
+        assert_eq!(env.run(None), 1);
+        assert!(env.refresh_rule("no_such_rule").is_err());
+    }
+
+    #[test]
+    fn focus() {
+        let env = Environment::new().unwrap();
+        env.load_string("(defmodule A) (defmodule B)").unwrap();
+        env.eval("(focus A)").unwrap();
+        assert_eq!(env.get_focus(), Some(String::from("A")));
+        env.eval("(focus B)").unwrap();
+        assert_eq!(env.get_focus(), Some(String::from("B")));
+        assert_eq!(env.pop_focus(), Some(String::from("B")));
+        assert_eq!(env.get_focus(), Some(String::from("A")));
+    }
+
+    #[test]
+    fn set_rule_salience() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (defglobal ?*fired* = (create$))
+        (deftemplate tpl1)
+        (defrule rule1 (tpl1) => (bind ?*fired* (create$ ?*fired* rule1)))
+        (defrule rule2 (declare (salience 10)) (tpl1) => (bind ?*fired* (create$ ?*fired* rule2)))
+        "#).unwrap();
+        env.new_fact_builder("tpl1").assert().unwrap();
+        env.set_rule_salience("rule1", 20).unwrap();
+        env.run(None);
+        assert_eq!(env.defglobal_value("fired").map(|v| OwnedValue::value(&v)).unwrap(),
+                   Some(OwnedValue::Multifield(vec![OwnedValue::Symbol(String::from("rule1")),
+                                                    OwnedValue::Symbol(String::from("rule2"))])));
+        assert!(env.set_rule_salience("rule1", 10001).is_err());
+        assert!(env.set_rule_salience("no_such_rule", 0).is_err());
+        env.load_string("(defrule rule3 (declare (salience (+ 1 2))) (tpl1) =>)").unwrap();
+        assert!(env.set_rule_salience("rule3", 0).is_err());
+    }
+
+    #[test]
+    fn agenda_changed() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate tpl1)
+        (defrule rule1 (tpl1) => )
+        "#).unwrap();
+        env.clear_agenda_changed();
+        assert!(!env.agenda_changed());
+        env.new_fact_builder("tpl1").assert().unwrap();
+        assert!(env.agenda_changed());
+        env.clear_agenda_changed();
+        assert!(!env.agenda_changed());
+    }
+
+    enum Item {
+        Int(i64),
+        Str(&'static str),
+    }
+
+    struct AssertedItem<'env>(Fact<'env>);
+
+    impl<'env> ::std::ops::Deref for AssertedItem<'env> {
+        type Target = Fact<'env>;
+        fn deref(&self) -> &Self::Target {
+            &self.0
+        }
+    }
+
+    impl<'env> Assertable<'env> for Item {
+        type T = AssertedItem<'env>;
+        type Error = ();
+
+        fn assert(self, env: &'env Environment) -> Result<Self::T, Self::Error> {
+            let fb = env.new_fact_builder("f1");
+            match self {
+                Item::Int(i) => fb.put("a", i),
+                Item::Str(s) => fb.put("a", s),
+            }.or(Err(()))?;
+            fb.assert().map(AssertedItem)
+        }
+    }
+
+    impl<'env> FactType<'env> for Item {
+        type Asserted = AssertedItem<'env>;
+        fn template_name() -> &'static str {
+            "f1"
+        }
+        fn wrap(fact: Fact<'env>) -> Self::Asserted {
+            AssertedItem(fact)
+        }
+    }
+
+    #[test]
+    fn facts_of() {
+        let env = Environment::new().unwrap();
+        assert!(env.facts_of::<Item>().is_empty());
+        env.load_string("(deftemplate f1 (slot a)) (deftemplate f2)").unwrap();
+        env.try_assert_all(vec![Item::Int(1), Item::Int(2)], false).unwrap();
+        env.new_fact_builder("f2").assert().unwrap();
+        let facts = env.facts_of::<Item>();
+        assert_eq!(facts.len(), 2);
+        assert_eq!(i64::value(&facts[1].slot("a")), Some(2));
+    }
+
+    #[test]
+    fn facts_with_template() {
+        let env = Environment::new().unwrap();
+        assert!(env.facts_with_template("f1").is_empty());
+        env.load_string("(deftemplate f1 (slot a)) (deftemplate f2)").unwrap();
+        env.try_assert_all(vec![Item::Int(1), Item::Int(2)], false).unwrap();
+        env.new_fact_builder("f2").assert().unwrap();
+        let facts = env.facts_with_template("f1");
+        assert_eq!(facts.len(), 2);
+        assert!(facts.iter().all(|f| f.template().name() == "f1"));
+        assert_eq!(env.facts_with_template("f2").len(), 1);
+    }
+
+    #[test]
+    fn find_all_facts() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate person (slot name) (slot age))").unwrap();
+        for &(name, age) in [("a", 25), ("b", 31), ("c", 40)].iter() {
+            let fb = env.new_fact_builder("person");
+            fb.put("name", name).unwrap();
+            fb.put("age", age).unwrap();
+            fb.assert().unwrap();
+        }
+        let facts = env.find_all_facts("person", "(> ?f:age 30)");
+        assert_eq!(facts.len(), 2);
+        assert!(facts.iter().all(|f| i64::value(&f.slot("age")).unwrap() > 30));
+        assert!(env.find_all_facts("person", "(> ?f:age 50)").is_empty());
+        assert!(env.find_all_facts("no_such_template", "TRUE").is_empty());
+    }
+
+    clips_fact_enum! {
+        enum AnyFact {
+            Item(Item),
+        }
+    }
+
+    #[test]
+    fn fact_enum() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a)) (deftemplate f2)").unwrap();
+        env.try_assert_all(vec![Item::Int(1)], false).unwrap();
+        env.new_fact_builder("f2").assert().unwrap();
+        let facts: Vec<_> = env.fact_iter().map(AnyFact::from).collect();
+        match facts[0] {
+            AnyFact::Item(ref item) => assert_eq!(i64::value(&item.slot("a")), Some(1)),
+            _ => panic!("f1 is expected to be an item"),
+        }
+        match facts[1] {
+            AnyFact::Other(ref fact) => assert!(fact.is_template("f2")),
+            _ => panic!("f2 is expected to be other"),
+        }
+    }
+
+    #[test]
+    fn assert_unique() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a))").unwrap();
+        let first = env.assert_unique(Item::Int(1)).unwrap();
+        let second = env.assert_unique(Item::Int(1)).unwrap();
+        assert_eq!(first.index(), second.index());
+        assert_eq!(env.number_of_facts(), 1);
+        env.assert_unique(Item::Int(2)).unwrap();
+        assert_eq!(env.number_of_facts(), 2);
+    }
+
+    #[test]
+    fn assert_unique_with_fact_duplication() {
+        let env = Environment::new().unwrap();
+        env.eval("(set-fact-duplication TRUE)").unwrap();
+        env.load_string("(deftemplate f1 (slot a))").unwrap();
+        let first = env.assert_unique(Item::Int(1)).unwrap();
+        let second = env.assert_unique(Item::Int(1)).unwrap();
+        assert_eq!(first.index(), second.index());
+        assert_eq!(env.number_of_facts(), 1);
+        // fact duplication is restored
+        assert_eq!(bool::value(&env.eval("(get-fact-duplication)").unwrap()), Some(true));
+        // no fact index is used up by the duplicate
+        let third = env.assert_unique(Item::Int(2)).unwrap();
+        assert_eq!(third.index(), first.index() + 1);
+    }
+
+    #[test]
+    fn assert_unique_no_side_effects() {
+        let env = Environment::new().unwrap();
+        env.eval("(set-fact-duplication TRUE)").unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        (defrule r (f1) =>)
+        "#).unwrap();
+        env.assert_unique(Item::Int(1)).unwrap();
+        env.clear_agenda_changed();
+        env.assert_unique(Item::Int(1)).unwrap();
+        assert!(!env.agenda_changed());
+        assert_eq!(env.number_of_activations(), 1);
+    }
+
+    #[test]
+    fn try_assert_all() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a (type INTEGER)))").unwrap();
+        let facts = env.try_assert_all(vec![Item::Int(1), Item::Int(2)], false).unwrap();
+        assert_eq!(facts.len(), 2);
+        assert_eq!(env.number_of_facts(), 2);
+    }
+
+    #[test]
+    fn try_assert_all_failure() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a (type INTEGER)))").unwrap();
+        let items = vec![Item::Int(1), Item::Int(2), Item::Str("3"), Item::Int(4)];
+        assert_eq!(env.try_assert_all(items, false).err(), Some((2, ())));
+        assert_eq!(env.number_of_facts(), 2);
+    }
+
+    #[test]
+    fn try_assert_all_atomic() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a (type INTEGER)))").unwrap();
+        let items = vec![Item::Int(1), Item::Int(2), Item::Str("3"), Item::Int(4)];
+        assert_eq!(env.try_assert_all(items, true).err(), Some((2, ())));
+        assert_eq!(env.number_of_facts(), 0);
+    }
+
+    #[test]
+    fn retract_all_facts() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a))").unwrap();
+        env.try_assert_all(vec![Item::Int(1), Item::Int(2), Item::Int(3)], false).unwrap();
+        assert_eq!(env.retract_all_facts(), 3);
+        assert_eq!(env.number_of_facts(), 0);
+        assert!(env.find_template("f1").is_some());
+        assert_eq!(env.retract_all_facts(), 0);
+    }
+
+    #[test]
+    fn retract_all_facts_logical() {
+        let env = Environment::new().unwrap();
+        env.load_string(r#"
+        (deftemplate f1 (slot a))
+        (deftemplate f2 (slot a))
+        (defrule r (logical (f1 (a ?a))) => (assert (f2 (a ?a))))
+        "#).unwrap();
+        env.try_assert_all(vec![Item::Int(1), Item::Int(2)], false).unwrap();
+        env.run(None);
+        assert_eq!(env.number_of_facts(), 4);
+        assert_eq!(env.retract_all_facts(), 4);
+        assert_eq!(env.number_of_facts(), 0);
+    }
+
     #[test]
     fn find_template() {
         let env = Environment::new().unwrap();
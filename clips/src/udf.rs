@@ -0,0 +1,283 @@
+use super::Environment;
+use super::value::{Value, EnvAllocatable};
+use sys;
+use std::ffi::CStr;
+use std::rc::Rc;
+use std::os::raw::{c_char, c_uint, c_void};
+
+/// Argument (or return value) type used in UDF type restrictions
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArgType {
+    Boolean,
+    Float,
+    Integer,
+    Symbol,
+    String,
+    InstanceName,
+    InstanceAddress,
+    FactAddress,
+    ExternalAddress,
+    Multifield,
+    Void,
+    /// Any type
+    Any,
+}
+
+impl ArgType {
+    /// Type restriction code understood by CLIPS
+    pub fn code(&self) -> char {
+        match *self {
+            ArgType::Boolean => 'b',
+            ArgType::Float => 'd',
+            ArgType::Integer => 'l',
+            ArgType::Symbol => 'y',
+            ArgType::String => 's',
+            ArgType::InstanceName => 'n',
+            ArgType::InstanceAddress => 'i',
+            ArgType::FactAddress => 'f',
+            ArgType::ExternalAddress => 'e',
+            ArgType::Multifield => 'm',
+            ArgType::Void => 'v',
+            ArgType::Any => '*',
+        }
+    }
+}
+
+fn codes(types: &[ArgType]) -> String {
+    types.iter().map(ArgType::code).collect()
+}
+
+/// Arity and type restrictions of a user-defined function,
+/// assembled into restriction strings passed to CLIPS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signature {
+    returns: Vec<ArgType>,
+    min_args: u16,
+    max_args: Option<u16>,
+    args: Vec<ArgType>,
+    positional: Vec<Vec<ArgType>>,
+}
+
+impl Signature {
+
+    /// Signature accepting any number of arguments of any type
+    /// and returning any type
+    pub fn new() -> Self {
+        Signature {
+            returns: vec![],
+            min_args: 0,
+            max_args: None,
+            args: vec![],
+            positional: vec![],
+        }
+    }
+
+    /// Restricts the return type
+    pub fn returns(mut self, types: &[ArgType]) -> Self {
+        self.returns = types.to_vec();
+        self
+    }
+
+    /// Sets minimum and (optionally) maximum number of arguments
+    pub fn arity(mut self, min: u16, max: Option<u16>) -> Self {
+        self.min_args = min;
+        self.max_args = max;
+        self
+    }
+
+    /// Restricts types of all arguments not restricted by `arg`
+    pub fn args(mut self, types: &[ArgType]) -> Self {
+        self.args = types.to_vec();
+        self
+    }
+
+    /// Restricts the type of the next positional argument
+    pub fn arg(mut self, types: &[ArgType]) -> Self {
+        self.positional.push(types.to_vec());
+        self
+    }
+
+    /// Return type restriction string
+    pub fn return_types(&self) -> String {
+        codes(&self.returns)
+    }
+
+    /// Argument type restriction string
+    pub fn argument_types(&self) -> String {
+        let mut result = codes(&self.args);
+        for arg in self.positional.iter() {
+            result.push(';');
+            result.push_str(&codes(arg));
+        }
+        result
+    }
+
+}
+
+impl Default for Signature {
+    fn default() -> Self {
+        Signature::new()
+    }
+}
+
+enum_from_primitive! {
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Error)]
+pub enum AddUDFError {
+    MinExceedsMax = sys::AddUDFError::AUE_MIN_EXCEEDS_MAX_ERROR as isize,
+    FunctionNameInUse = sys::AddUDFError::AUE_FUNCTION_NAME_IN_USE_ERROR as isize,
+    InvalidArgumentType = sys::AddUDFError::AUE_INVALID_ARGUMENT_TYPE_ERROR as isize,
+    InvalidReturnType = sys::AddUDFError::AUE_INVALID_RETURN_TYPE_ERROR as isize,
+}
+}
+
+pub(crate) type Function = Rc<dyn Fn(&Environment, &[Value]) -> Result<Value, ()>>;
+
+const ANY_TYPE_BITS: c_uint = 0x7ff;
+
+// CLIPS keeps this pointer, so it has to be static
+const UDF_NAME: &[u8] = b"clips_rs_udf\0";
+
+pub(crate) unsafe extern "C" fn call(env: *mut sys::Environment, context: *mut sys::UDFContext,
+                                     out: *mut sys::UDFValue) {
+    // UDFs are registered with the handle of their environment
+    let environment = &*((*context).context as *const Environment);
+    let name = CStr::from_ptr((*(*(*context).theFunction).callFunctionName).contents).to_string_lossy();
+    let f = environment.state.udfs.borrow().get(&*name).cloned();
+    let f = match f {
+        Some(f) => f,
+        None => {
+            sys::UDFThrowError(context);
+            (*out).__bindgen_anon_1.value = sys::CreateBoolean(env, false) as *mut c_void;
+            return;
+        },
+    };
+    let count = sys::UDFArgumentCount(context);
+    let mut args = Vec::with_capacity(count as usize);
+    for i in 1..count + 1 {
+        let mut arg: sys::UDFValue = ::std::mem::zeroed();
+        if !sys::UDFNthArgument(context, i, ANY_TYPE_BITS, &mut arg) {
+            return;
+        }
+        args.push(Value::new(sys::clipsValue__bindgen_ty_1 { value: arg.__bindgen_anon_1.value }));
+    }
+    // panics can't unwind into CLIPS, so they are treated as errors
+    let result = ::std::panic::catch_unwind(::std::panic::AssertUnwindSafe(|| f(environment, &args)))
+        .unwrap_or(Err(()));
+    match result {
+        Ok(val) => (*out).__bindgen_anon_1.value = val.0.__bindgen_anon_1.value,
+        Err(()) => {
+            sys::UDFThrowError(context);
+            (*out).__bindgen_anon_1.value = sys::CreateBoolean(env, false) as *mut c_void;
+        },
+    }
+}
+
+impl Environment {
+
+    /// Registers a user-defined function
+    ///
+    /// Arguments are checked against `signature` by CLIPS before `f`
+    /// is called. Returning `Err(())` from `f` (or panicking in it)
+    /// raises an evaluation error.
+    pub fn add_udf<S, F, R>(&self, name: S, signature: &Signature, f: F) -> Result<(), AddUDFError>
+        where S: AsRef<str>, R: EnvAllocatable, F: Fn(&Environment, &[Value]) -> Result<R, ()> + 'static {
+        use std::ffi::CString;
+        use enum_primitive::FromPrimitive;
+        let name = CString::new(name.as_ref()).unwrap();
+        let return_types = CString::new(signature.return_types()).unwrap();
+        let argument_types = CString::new(signature.argument_types()).unwrap();
+        let function: Function = Rc::new(move |env: &Environment, args: &[Value]| {
+            f(env, args).map(|v| v.allocate(env))
+        });
+        let return_code = unsafe {
+            sys::AddUDF(self.env, name.as_ptr(),
+                        if signature.returns.is_empty() { ::std::ptr::null() } else { return_types.as_ptr() },
+                        signature.min_args, signature.max_args.unwrap_or(u16::MAX),
+                        if signature.args.is_empty() && signature.positional.is_empty() {
+                            ::std::ptr::null()
+                        } else {
+                            argument_types.as_ptr()
+                        },
+                        Some(call), UDF_NAME.as_ptr() as *const c_char, self.handle() as *mut c_void)
+        };
+        match return_code {
+            sys::AddUDFError::AUE_NO_ERROR => {
+                self.state.udfs.borrow_mut().insert(name.to_string_lossy().into_owned(), function);
+                Ok(())
+            },
+            err => Err(AddUDFError::from_isize(err as isize).expect("valid return code")),
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+    use super::*;
+
+    #[test]
+    fn restriction_strings() {
+        let sig = Signature::new()
+            .returns(&[ArgType::Integer, ArgType::Float])
+            .args(&[ArgType::Any])
+            .arg(&[ArgType::Symbol, ArgType::String])
+            .arg(&[ArgType::Multifield]);
+        assert_eq!(sig.return_types(), "ld");
+        assert_eq!(sig.argument_types(), "*;ys;m");
+    }
+
+    #[test]
+    fn typed_udf() {
+        let env = Environment::new().unwrap();
+        let sig = Signature::new()
+            .returns(&[ArgType::Integer])
+            .arity(2, Some(2))
+            .args(&[ArgType::Integer]);
+        env.add_udf("add2", &sig, |_, args| {
+            Ok(args[0].as_number::<i64>().unwrap() + args[1].as_number::<i64>().unwrap())
+        }).unwrap();
+        assert_eq!(env.eval("(add2 1 2)").unwrap().as_number::<i64>(), Some(3));
+        assert!(env.eval("(add2 1 \"2\")").is_err());
+        assert!(env.eval("(add2 1)").is_err());
+    }
+
+    #[test]
+    fn udf_error() {
+        let env = Environment::new().unwrap();
+        env.add_udf("fail", &Signature::new(), |_, _| Err::<i64, ()>(())).unwrap();
+        assert!(env.eval("(fail)").is_err());
+    }
+
+    #[test]
+    fn udf_environment() {
+        let env = Environment::new().unwrap();
+        // the function is called with the environment it is registered in
+        env.add_udf("in-gc-block", &Signature::new(), |env, _| {
+            Ok(env.state.retained.borrow().is_some())
+        }).unwrap();
+        assert_eq!(bool::value(&env.eval("(in-gc-block)").unwrap()), Some(false));
+        // moving the environment doesn't invalidate its functions
+        let env = Box::new(env);
+        env.with_gc_disabled(|env| {
+            assert_eq!(bool::value(&env.eval("(in-gc-block)").unwrap()), Some(true));
+        });
+    }
+
+    #[test]
+    fn udf_panic() {
+        let env = Environment::new().unwrap();
+        env.add_udf("panic", &Signature::new(), |_, _| -> Result<i64, ()> { panic!("in udf") }).unwrap();
+        assert!(env.eval("(panic)").is_err());
+        assert_eq!(env.eval("(+ 1 2)").unwrap().as_number::<i64>(), Some(3));
+    }
+
+    #[test]
+    fn duplicate_udf() {
+        let env = Environment::new().unwrap();
+        env.add_udf("f", &Signature::new(), |_, _| Ok(1)).unwrap();
+        assert_eq!(env.add_udf("f", &Signature::new(), |_, _| Ok(1)).err(),
+                   Some(AddUDFError::FunctionNameInUse));
+    }
+
+}
@@ -1,5 +1,9 @@
 use sys;
 use std::ffi::{CStr, CString};
+use std::convert::TryFrom;
+use std::fmt;
+use std::marker::PhantomData;
+use std::borrow::Cow;
 
 /// CLIPS value
 pub struct Value(pub(crate) sys::CLIPSValue);
@@ -33,10 +37,76 @@ pub enum Type {
 }
 
 impl Value {
+    /// Underlying CLIPS value, for use with `clips-sys`
+    pub fn as_raw(&self) -> &sys::CLIPSValue {
+        &self.0
+    }
+
+    /// Wraps a CLIPS value obtained through `clips-sys`
+    ///
+    /// # Safety
+    ///
+    /// The value remains owned by CLIPS (the resulting `Value` doesn't
+    /// retain or release it), so it must point to a live CLIPS value of
+    /// the same environment and stay valid for as long as the resulting
+    /// `Value` is used, that is, until it is garbage-collected (see
+    /// `Environment::with_gc_disabled`) or its owner (such as a fact)
+    /// is removed
+    pub unsafe fn from_raw(val: sys::CLIPSValue) -> Value {
+        Value(val)
+    }
+
     /// Value's type
     pub fn type_of(&self) -> Type {
         unsafe { Type::from_u16((*self.0.__bindgen_anon_1.header).type_).unwrap() }
     }
+
+    /// Returns an iterator over multifield's elements,
+    /// `None` if the value is not a multifield
+    pub(crate) fn multifield_iter(&self) -> Option<MultifieldIter> {
+        match self.type_of() {
+            Type::Multifield => Some(MultifieldIter {
+                multifield: unsafe { self.0.__bindgen_anon_1.multifieldValue },
+                index: 0,
+                value: PhantomData,
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Iterator over multifield's elements
+pub(crate) struct MultifieldIter<'a> {
+    multifield: *mut sys::Multifield,
+    index: usize,
+    value: PhantomData<&'a Value>,
+}
+
+impl<'a> MultifieldIter<'a> {
+    /// Creates an iterator over a multifield that is known
+    /// to outlive `'a`
+    pub(crate) unsafe fn new(multifield: *mut sys::Multifield) -> Self {
+        MultifieldIter {
+            multifield,
+            index: 0,
+            value: PhantomData,
+        }
+    }
+}
+
+impl<'a> Iterator for MultifieldIter<'a> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            if self.index >= (*self.multifield).length {
+                return None;
+            }
+            let val = *(*self.multifield).contents.as_ptr().add(self.index);
+            self.index += 1;
+            Some(Value(val))
+        }
+    }
 }
 
 /// Allows accessing typed values inside of `Value`,
@@ -47,12 +117,91 @@ pub trait ValueAccess : Sized {
     fn value(val: &Value) -> Option<Self>;
 }
 
+/// Numeric types CLIPS numbers can be converted to
+/// (see `Value::as_number`)
+pub trait Number : Sized {
+    /// Converts a CLIPS integer, returns `None` if it is out of range
+    fn from_integer(i: i64) -> Option<Self>;
+    /// Converts a CLIPS float, returns `None` if it is out of range
+    fn from_float(f: f64) -> Option<Self>;
+}
+
+// Floats are rounded to the nearest integer (half away from zero),
+// non-finite floats and values that don't fit into the target
+// type lead to `None`
+macro_rules! number_for_int {
+    ($ty: ty) => {
+    impl Number for $ty {
+        fn from_integer(i: i64) -> Option<$ty> {
+            <$ty>::try_from(i).ok()
+        }
+        fn from_float(f: f64) -> Option<$ty> {
+            let f = f.round();
+            if f.is_finite() && f >= <$ty>::MIN as f64 && f < <$ty>::MAX as f64 + 1.0 {
+                Some(f as $ty)
+            } else {
+                None
+            }
+        }
+    }
+    };
+}
+
+number_for_int!(i8);
+number_for_int!(i16);
+number_for_int!(i32);
+number_for_int!(i64);
+number_for_int!(u8);
+number_for_int!(u16);
+number_for_int!(u32);
+number_for_int!(u64);
+number_for_int!(isize);
+number_for_int!(usize);
+
+impl Number for f64 {
+    fn from_integer(i: i64) -> Option<f64> {
+        Some(i as f64)
+    }
+    fn from_float(f: f64) -> Option<f64> {
+        Some(f)
+    }
+}
+
+impl Number for f32 {
+    fn from_integer(i: i64) -> Option<f32> {
+        Some(i as f32)
+    }
+    /// Returns `None` if a finite value is outside of `f32` range.
+    /// Infinite values are returned as is
+    fn from_float(f: f64) -> Option<f32> {
+        if f.is_finite() && f.abs() > f32::MAX as f64 {
+            None
+        } else {
+            Some(f as f32)
+        }
+    }
+}
+
+impl Value {
+    /// Converts an integer or a float to any numeric type,
+    /// following the conversion rules of `Number`.
+    /// Returns `None` for non-numeric values
+    pub fn as_number<T: Number>(&self) -> Option<T> {
+        match self.type_of() {
+            Type::Integer => T::from_integer(unsafe { (*self.0.__bindgen_anon_1.integerValue).contents }),
+            Type::Float => T::from_float(unsafe { (*self.0.__bindgen_anon_1.floatValue).contents }),
+            _ => None,
+        }
+    }
+}
+
+// Integers that don't fit into the target type lead to `None`
 macro_rules! value_access_for_int {
     ($ty: ty) => {
     impl ValueAccess for $ty {
         fn value(val: &Value) -> Option<$ty> {
             match val.type_of() {
-                Type::Integer => unsafe { Some((*val.0.__bindgen_anon_1.integerValue).contents as $ty) },
+                Type::Integer => val.as_number(),
                 _ => None,
             }
         }
@@ -68,13 +217,15 @@ value_access_for_int!(u8);
 value_access_for_int!(u16);
 value_access_for_int!(u32);
 value_access_for_int!(u64);
+value_access_for_int!(isize);
+value_access_for_int!(usize);
 
 macro_rules! value_access_for_float {
     ($ty: ty) => {
     impl ValueAccess for $ty {
         fn value(val: &Value) -> Option<$ty> {
             match val.type_of() {
-                Type::Float => unsafe { Some((*val.0.__bindgen_anon_1.floatValue).contents as $ty) },
+                Type::Float => val.as_number(),
                 _ => None,
             }
         }
@@ -105,6 +256,13 @@ impl ValueAccess for String {
     }
 }
 
+impl<'a> ValueAccess for Cow<'a, str> {
+    fn value(val: &Value) -> Option<Cow<'a, str>> {
+        let val: Option<&'a str> = ValueAccess::value(val);
+        val.map(Cow::Borrowed)
+    }
+}
+
 impl<'a> ValueAccess for Symbol<&'a str> {
     fn value(val: &Value) -> Option<Symbol<&'a str>> {
         match val.type_of() {
@@ -118,6 +276,37 @@ impl<'a> ValueAccess for Symbol<&'a str> {
     }
 }
 
+impl Value {
+    /// Reads a symbol as a boolean using custom conventions
+    /// (such as `yes`/`no`), returns `None` if the symbol is neither
+    /// in `truthy` nor in `falsy`
+    pub fn as_bool_with(&self, truthy: &[&str], falsy: &[&str]) -> Option<bool> {
+        Symbol::<&str>::value(self).and_then(|Symbol(s)| {
+            if truthy.contains(&s) {
+                Some(true)
+            } else if falsy.contains(&s) {
+                Some(false)
+            } else {
+                None
+            }
+        })
+    }
+}
+
+impl Value {
+    /// Printed representation of the value, exactly as CLIPS
+    /// would print it (for example, with `printout`)
+    pub fn pp_string(&self, env: &super::Environment) -> String {
+        let mut val = self.0;
+        env.capture_output(|name| {
+            let c_string = CString::new(name).unwrap();
+            unsafe {
+                sys::WriteCLIPSValue(env.env, c_string.as_ptr(), &mut val)
+            }
+        })
+    }
+}
+
 impl ValueAccess for bool {
     fn value(val: &Value) -> Option<bool> {
         let symbol = Symbol::<&str>::value(&val);
@@ -130,6 +319,73 @@ impl ValueAccess for bool {
 }
 
 
+/// CLIPS value detached from the environment
+///
+/// Unlike `Value`, it owns its contents and can outlive
+/// the fact or the evaluation it was obtained from
+#[derive(Debug, Clone, PartialEq)]
+pub enum OwnedValue {
+    Float(f64),
+    Integer(i64),
+    Symbol(String),
+    String(String),
+    InstanceName(String),
+    Multifield(Vec<OwnedValue>),
+    /// Fact address, represented by the fact's index
+    FactAddress(u64),
+    Void,
+}
+
+impl ValueAccess for OwnedValue {
+    /// Returns `None` for values that can't be detached from
+    /// the environment (external and instance addresses)
+    fn value(val: &Value) -> Option<OwnedValue> {
+        unsafe {
+            let v = &val.0.__bindgen_anon_1;
+            match val.type_of() {
+                Type::Float => Some(OwnedValue::Float((*v.floatValue).contents)),
+                Type::Integer => Some(OwnedValue::Integer((*v.integerValue).contents)),
+                Type::Symbol => Some(OwnedValue::Symbol(lexeme(val))),
+                Type::String => Some(OwnedValue::String(lexeme(val))),
+                Type::InstanceName => Some(OwnedValue::InstanceName(lexeme(val))),
+                Type::Multifield => val.multifield_iter()
+                    .and_then(|iter| iter.map(|v| OwnedValue::value(&v)).collect::<Option<Vec<_>>>())
+                    .map(OwnedValue::Multifield),
+                Type::FactAddress => Some(OwnedValue::FactAddress(sys::FactIndex(v.factValue) as u64)),
+                Type::Void => Some(OwnedValue::Void),
+                _ => None,
+            }
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl ::serde::Serialize for OwnedValue {
+    fn serialize<S: ::serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            OwnedValue::Float(f) => serializer.serialize_f64(f),
+            OwnedValue::Integer(i) => serializer.serialize_i64(i),
+            OwnedValue::Symbol(ref s) |
+            OwnedValue::String(ref s) |
+            OwnedValue::InstanceName(ref s) => serializer.serialize_str(s),
+            OwnedValue::Multifield(ref values) => ::serde::Serialize::serialize(values, serializer),
+            OwnedValue::FactAddress(index) => serializer.serialize_u64(index),
+            OwnedValue::Void => serializer.serialize_unit(),
+        }
+    }
+}
+
+/// Reads the contents of a lexeme (symbol, string or instance name)
+unsafe fn lexeme(val: &Value) -> String {
+    let str = (*val.0.__bindgen_anon_1.lexemeValue).contents;
+    String::from(CStr::from_ptr(str).to_str().unwrap())
+}
+
+
+/// Values that can be allocated in an environment
+///
+/// Allocating unsigned integers above `i64::MAX` (the range
+/// of CLIPS integers) panics
 pub trait EnvAllocatable {
     fn allocate(&self, env: &super::Environment) -> Value;
 }
@@ -139,8 +395,9 @@ macro_rules! impl_env_allocatable_for_integer {
       impl EnvAllocatable for $t {
 
         fn allocate(&self, env: &super::Environment) -> Value {
+            let int = i64::try_from(*self).expect("integer is out of range of CLIPS integers (i64)");
             let int = unsafe {
-                sys::CreateInteger(env.env, *self as i64)
+                sys::CreateInteger(env.env, int)
             };
             Value::new(sys::clipsValue__bindgen_ty_1 {
                integerValue: int
@@ -158,6 +415,8 @@ impl_env_allocatable_for_integer!(u32);
 impl_env_allocatable_for_integer!(i32);
 impl_env_allocatable_for_integer!(u64);
 impl_env_allocatable_for_integer!(i64);
+impl_env_allocatable_for_integer!(usize);
+impl_env_allocatable_for_integer!(isize);
 
 macro_rules! impl_env_allocatable_for_float {
     ($t: ty) => {
@@ -191,6 +450,12 @@ impl<'a, 'b> EnvAllocatable for &'b String {
     }
 }
 
+impl<'a> EnvAllocatable for Cow<'a, str> {
+    fn allocate(&self, env: &super::Environment) -> Value {
+        self.as_ref().allocate(env)
+    }
+}
+
 impl<'a> EnvAllocatable for &'a str {
 
   fn allocate(&self, env: &super::Environment) -> Value {
@@ -204,6 +469,34 @@ impl<'a> EnvAllocatable for &'a str {
   }
 }
 
+impl<'a, T: EnvAllocatable> EnvAllocatable for &'a [T] {
+    fn allocate(&self, env: &super::Environment) -> Value {
+        let mb = unsafe {
+            sys::CreateMultifieldBuilder(env.env, self.len())
+        };
+        for item in self.iter() {
+            let mut val = item.allocate(env);
+            unsafe {
+                sys::MBAppend(mb, &mut val.0)
+            }
+        }
+        let multifield = unsafe {
+            let multifield = sys::MBCreate(mb);
+            sys::MBDispose(mb);
+            multifield
+        };
+        Value::new(sys::clipsValue__bindgen_ty_1 {
+            multifieldValue: multifield
+        })
+    }
+}
+
+impl<T: EnvAllocatable> EnvAllocatable for Vec<T> {
+    fn allocate(&self, env: &super::Environment) -> Value {
+        self.as_slice().allocate(env)
+    }
+}
+
 #[derive(Eq, PartialEq, Debug)]
 pub struct Symbol<S: AsRef<str>>(pub S);
 
@@ -238,6 +531,155 @@ impl EnvAllocatable for bool {
 
 
 
+/// Writes a string as a CLIPS string literal, quoting it
+/// and escaping quotes and backslashes
+pub fn escape_string(s: &str) -> String {
+    let mut result = String::with_capacity(s.len() + 2);
+    result.push('"');
+    for c in s.chars() {
+        if c == '"' || c == '\\' {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result.push('"');
+    result
+}
+
+/// Writes a symbol in CLIPS syntax.
+///
+/// Symbols that can't be written as is (empty ones, ones containing
+/// delimiters or looking like numbers or variables) are written as
+/// a `(sym-cat "...")` call, so they are only usable where CLIPS
+/// evaluates expressions
+pub fn escape_symbol(s: &str) -> String {
+    let lexical = !s.is_empty() &&
+        !s.starts_with('?') &&
+        !s.starts_with("$?") &&
+        !is_number(s) &&
+        !s.chars().any(|c| c.is_whitespace() || c.is_control() || "\"()&|<~;".contains(c));
+    if lexical {
+        String::from(s)
+    } else {
+        format!("(sym-cat {})", escape_string(s))
+    }
+}
+
+/// Checks if the string is read by CLIPS as a number: an optional
+/// sign, digits with an optional decimal point and an optional exponent
+fn is_number(s: &str) -> bool {
+    let s = s.strip_prefix(['+', '-']).unwrap_or(s);
+    let (mantissa, exponent) = match s.find(['e', 'E']) {
+        Some(index) => (&s[..index], Some(&s[index + 1..])),
+        None => (s, None),
+    };
+    let digits = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_digit());
+    let mantissa = match mantissa.find('.') {
+        Some(index) => {
+            let (int, frac) = (&mantissa[..index], &mantissa[index + 1..]);
+            (int.is_empty() || digits(int)) && (frac.is_empty() || digits(frac)) && (int.len() + frac.len() > 0)
+        },
+        None => digits(mantissa),
+    };
+    mantissa && exponent.is_none_or(|e| digits(e.strip_prefix(['+', '-']).unwrap_or(e)))
+}
+
+/// Formats Rust values the way they are written in CLIPS syntax
+pub trait ClipsDisplay {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+macro_rules! clips_display_for_int {
+    ($ty: ty) => {
+    impl ClipsDisplay for $ty {
+        fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{}", self)
+        }
+    }
+    };
+}
+
+clips_display_for_int!(i8);
+clips_display_for_int!(i16);
+clips_display_for_int!(i32);
+clips_display_for_int!(i64);
+clips_display_for_int!(u8);
+clips_display_for_int!(u16);
+clips_display_for_int!(u32);
+clips_display_for_int!(u64);
+clips_display_for_int!(isize);
+clips_display_for_int!(usize);
+
+macro_rules! clips_display_for_float {
+    ($ty: ty) => {
+    impl ClipsDisplay for $ty {
+        // `Debug` always keeps the decimal point, so
+        // the value is not mistaken for an integer
+        fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "{:?}", self)
+        }
+    }
+    };
+}
+
+clips_display_for_float!(f32);
+clips_display_for_float!(f64);
+
+impl ClipsDisplay for str {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&escape_string(self))
+    }
+}
+
+impl ClipsDisplay for String {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt_clips(f)
+    }
+}
+
+impl<S: AsRef<str>> ClipsDisplay for Symbol<S> {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&escape_symbol(self.0.as_ref()))
+    }
+}
+
+impl ClipsDisplay for bool {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(if *self { "TRUE" } else { "FALSE" })
+    }
+}
+
+/// Multifields are written as their space-separated elements, fact
+/// addresses in their printed form (which can't be read back)
+impl ClipsDisplay for OwnedValue {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            OwnedValue::Float(v) => v.fmt_clips(f),
+            OwnedValue::Integer(v) => v.fmt_clips(f),
+            OwnedValue::Symbol(ref v) => Symbol(v).fmt_clips(f),
+            OwnedValue::String(ref v) => v.fmt_clips(f),
+            OwnedValue::InstanceName(ref v) => write!(f, "[{}]", v),
+            OwnedValue::Multifield(ref values) => {
+                for (i, v) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" ")?;
+                    }
+                    v.fmt_clips(f)?;
+                }
+                Ok(())
+            },
+            OwnedValue::FactAddress(index) => write!(f, "<Fact-{}>", index),
+            OwnedValue::Void => Ok(()),
+        }
+    }
+}
+
+impl<'a, T: ClipsDisplay + ?Sized> ClipsDisplay for &'a T {
+    fn fmt_clips(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt_clips(f)
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -255,6 +697,8 @@ mod tests {
         assert_eq!(0i32.allocate(&env).type_of(), Type::Integer);
         assert_eq!(0u64.allocate(&env).type_of(), Type::Integer);
         assert_eq!(0i64.allocate(&env).type_of(), Type::Integer);
+        assert_eq!(0usize.allocate(&env).type_of(), Type::Integer);
+        assert_eq!(0isize.allocate(&env).type_of(), Type::Integer);
     }
 
     #[test]
@@ -265,6 +709,57 @@ mod tests {
         assert_eq!(access, 1);
     }
 
+    #[test]
+    pub fn usize_value_access() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f (slot count))").unwrap();
+        let fb = env.new_fact_builder("f");
+        fb.put("count", 42usize).unwrap();
+        let fact = fb.assert().unwrap();
+        assert_eq!(usize::value(&fact.slot("count")), Some(42));
+        // boundary
+        let val = (i64::MAX as usize).allocate(&env);
+        assert_eq!(usize::value(&val), Some(i64::MAX as usize));
+        // out of range values lead to None
+        let val = (-1i64).allocate(&env);
+        assert!(usize::value(&val).is_none());
+        assert_eq!(isize::value(&val), Some(-1));
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    pub fn usize_above_i64_max() {
+        let env = Environment::new().unwrap();
+        (i64::MAX as usize + 1).allocate(&env);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    pub fn u64_max() {
+        let env = Environment::new().unwrap();
+        u64::MAX.allocate(&env);
+    }
+
+    #[test]
+    pub fn as_number() {
+        let env = Environment::new().unwrap();
+        let val = 300.allocate(&env);
+        assert_eq!(val.as_number::<i64>(), Some(300));
+        assert_eq!(val.as_number::<u16>(), Some(300));
+        assert_eq!(val.as_number::<u8>(), None);
+        assert_eq!(val.as_number::<f64>(), Some(300.0));
+        let val = 2.5.allocate(&env);
+        assert_eq!(val.as_number::<i64>(), Some(3));
+        assert_eq!(val.as_number::<f32>(), Some(2.5));
+        let val = (-2.5).allocate(&env);
+        assert_eq!(val.as_number::<i64>(), Some(-3));
+        assert_eq!(val.as_number::<u64>(), None);
+        let val = f64::INFINITY.allocate(&env);
+        assert_eq!(val.as_number::<i64>(), None);
+        let val = "1".allocate(&env);
+        assert_eq!(val.as_number::<i64>(), None);
+    }
+
     #[test]
     pub fn float() {
         let env = Environment::new().unwrap();
@@ -280,6 +775,17 @@ mod tests {
         assert_eq!(access, 1f64);
     }
 
+    #[test]
+    pub fn f32_value_access() {
+        let env = Environment::new().unwrap();
+        let val = 1.5f64.allocate(&env);
+        assert_eq!(f32::value(&val), Some(1.5));
+        let val = f64::MAX.allocate(&env);
+        assert!(f32::value(&val).is_none());
+        let val = f64::INFINITY.allocate(&env);
+        assert_eq!(f32::value(&val), Some(f32::INFINITY));
+    }
+
     #[test]
     pub fn string() {
         let env = Environment::new().unwrap();
@@ -294,6 +800,21 @@ mod tests {
         assert_eq!(access, "test");
     }
 
+    #[test]
+    pub fn cow_str() {
+        let env = Environment::new().unwrap();
+        env.load_string("(deftemplate f1 (slot a) (slot b))").unwrap();
+        let fb = env.new_fact_builder("f1");
+        fb.put("a", Cow::Borrowed("borrowed")).unwrap();
+        fb.put("b", Cow::Owned::<str>(String::from("owned"))).unwrap();
+        let fact = fb.assert().unwrap();
+        let a: Cow<str> = ValueAccess::value(&fact.slot("a")).unwrap();
+        let b: Cow<str> = ValueAccess::value(&fact.slot("b")).unwrap();
+        assert_eq!(a, "borrowed");
+        assert_eq!(b, "owned");
+        assert_eq!(Cow::<str>::value(&Symbol("a").allocate(&env)), None);
+    }
+
     #[test]
     pub fn symbol() {
         let env = Environment::new().unwrap();
@@ -327,5 +848,111 @@ mod tests {
         assert!(access.is_none());
     }
 
+    struct Clips<T: ClipsDisplay>(T);
+
+    impl<T: ClipsDisplay> fmt::Display for Clips<T> {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.0.fmt_clips(f)
+        }
+    }
+
+    #[test]
+    pub fn clips_display() {
+        assert_eq!(Clips(1).to_string(), "1");
+        assert_eq!(Clips(1f64).to_string(), "1.0");
+        assert_eq!(Clips(true).to_string(), "TRUE");
+        assert_eq!(Clips(Symbol("a")).to_string(), "a");
+        assert_eq!(Clips("a \"b\" \\").to_string(), r#""a \"b\" \\""#);
+    }
 
-}
\ No newline at end of file
+    #[test]
+    pub fn escaping() {
+        assert_eq!(escape_string(r#"say "hi" \ bye"#), r#""say \"hi\" \\ bye""#);
+        assert_eq!(escape_symbol("a"), "a");
+        assert_eq!(escape_symbol("a b"), r#"(sym-cat "a b")"#);
+        assert_eq!(escape_symbol("1"), r#"(sym-cat "1")"#);
+        assert_eq!(escape_symbol("-1.5e+3"), r#"(sym-cat "-1.5e+3")"#);
+        assert_eq!(escape_symbol("inf"), "inf");
+        assert_eq!(escape_symbol("nan"), "nan");
+        assert_eq!(escape_symbol("Infinity"), "Infinity");
+        assert_eq!(escape_symbol("1e"), "1e");
+        let env = Environment::new().unwrap();
+        let val = env.eval(escape_string(r#"say "hi" \ bye"#)).unwrap();
+        assert_eq!(String::value(&val).unwrap(), r#"say "hi" \ bye"#);
+        let val = env.eval(escape_symbol("a b")).unwrap();
+        assert_eq!(Symbol::<&str>::value(&val).unwrap(), Symbol("a b"));
+        for symbol in ["inf", "nan", "1e"].iter() {
+            let val = env.eval(escape_symbol(symbol)).unwrap();
+            assert_eq!(Symbol::<&str>::value(&val).unwrap(), Symbol(*symbol));
+        }
+    }
+
+    #[test]
+    pub fn multifield() {
+        let env = Environment::new().unwrap();
+        let val = vec![1, 2, 3].allocate(&env);
+        assert_eq!(val.type_of(), Type::Multifield);
+        assert_eq!(OwnedValue::value(&val), Some(OwnedValue::Multifield(vec![
+            OwnedValue::Integer(1),
+            OwnedValue::Integer(2),
+            OwnedValue::Integer(3),
+        ])));
+        let val = (&["a", "b"][..]).allocate(&env);
+        assert_eq!(OwnedValue::value(&val), Some(OwnedValue::Multifield(vec![
+            OwnedValue::String(String::from("a")),
+            OwnedValue::String(String::from("b")),
+        ])));
+    }
+
+    #[test]
+    pub fn raw() {
+        let env = Environment::new().unwrap();
+        let val = 42.allocate(&env);
+        let raw = *val.as_raw();
+        let val1 = unsafe { Value::from_raw(raw) };
+        assert_eq!(val1.type_of(), Type::Integer);
+        assert_eq!(i64::value(&val1), Some(42));
+    }
+
+    #[test]
+    pub fn custom_bool() {
+        let env = Environment::new().unwrap();
+        let val = Symbol("yes").allocate(&env);
+        assert_eq!(val.as_bool_with(&["yes", "on"], &["no", "off"]), Some(true));
+        assert!(bool::value(&val).is_none());
+        let val = Symbol("off").allocate(&env);
+        assert_eq!(val.as_bool_with(&["yes", "on"], &["no", "off"]), Some(false));
+        let val = Symbol("maybe").allocate(&env);
+        assert!(val.as_bool_with(&["yes", "on"], &["no", "off"]).is_none());
+    }
+
+    #[test]
+    pub fn owned_value_access() {
+        let env = Environment::new().unwrap();
+        assert_eq!(OwnedValue::value(&1.allocate(&env)), Some(OwnedValue::Integer(1)));
+        assert_eq!(OwnedValue::value(&1.5.allocate(&env)), Some(OwnedValue::Float(1.5)));
+        assert_eq!(OwnedValue::value(&"a".allocate(&env)), Some(OwnedValue::String(String::from("a"))));
+        assert_eq!(OwnedValue::value(&Symbol("a").allocate(&env)), Some(OwnedValue::Symbol(String::from("a"))));
+        let val = env.eval("(create$ 1 a \"b\")").unwrap();
+        assert_eq!(OwnedValue::value(&val), Some(OwnedValue::Multifield(vec![
+            OwnedValue::Integer(1),
+            OwnedValue::Symbol(String::from("a")),
+            OwnedValue::String(String::from("b")),
+        ])));
+    }
+
+
+    #[test]
+    fn pp_string() {
+        let env = Environment::new().unwrap();
+        let expr = r#"(create$ 1 2.0 1e20 a "b" [i])"#;
+        let pp = env.eval(expr).unwrap().pp_string(&env);
+        let printed = env.capture_output(|name| {
+            env.eval(format!("(printout {} {})", name, expr)).unwrap();
+        });
+        assert_eq!(pp, printed);
+        assert_eq!(pp, r#"(1 2.0 1e+20 a "b" [i])"#);
+        assert_eq!(env.eval("sym").unwrap().pp_string(&env), "sym");
+    }
+
+}
@@ -30,6 +30,14 @@ struct FactReceiver {
     consume_on_assert: bool,
     #[darling(default)]
     non_recoverable: bool,
+    #[darling(default)]
+    display: bool,
+    #[darling(default)]
+    run_on_assert: bool,
+    #[darling(default)]
+    slot_prefix: Option<String>,
+    #[darling(default)]
+    read_only: bool,
 }
 
 impl FactReceiver {
@@ -39,6 +47,13 @@ impl FactReceiver {
     fn asserted_type_name(&self) -> Ident {
         self.asserted_type_name.clone().unwrap_or(Ident::from(String::from("Asserted") + self.ident.as_ref()))
     }
+    fn slot_name(&self, field: &SlotReceiver) -> String {
+        match (&field.rename, &self.slot_prefix) {
+            (&Some(ref rename), _) => rename.clone(),
+            (&None, &Some(ref prefix)) => prefix.clone() + field.ident.clone().unwrap().as_ref(),
+            (&None, &None) => String::from(field.ident.clone().unwrap().as_ref()),
+        }
+    }
 }
 
 #[derive(FromMetaItem, Debug, Clone, Copy)]
@@ -63,6 +78,8 @@ impl ReturnType {
                    ty == &syn::parse_type("u16").unwrap()  ||
                    ty == &syn::parse_type("u32").unwrap()  ||
                    ty == &syn::parse_type("u64").unwrap()  ||
+                   ty == &syn::parse_type("isize").unwrap() ||
+                   ty == &syn::parse_type("usize").unwrap() ||
                    ty == &syn::parse_type("f32").unwrap()  ||
                    ty == &syn::parse_type("f64").unwrap()  {
                     return ReturnType::Copy
@@ -114,9 +131,6 @@ struct SlotReceiver {
 }
 
 impl SlotReceiver {
-    fn slot_name(&self) -> String {
-        self.rename.clone().unwrap_or( String::from(self.ident.clone().unwrap().as_ref()))
-    }
     fn return_ty(&self) -> syn::Ty {
         self.return_type.to_ty(self.ty.clone())
     }
@@ -229,7 +243,7 @@ impl<'a> ToTokens for AssertedImpl<'a> {
         let mut slots_tokens = Tokens::new();
         for field in fields.clone() {
             let field_name = field.ident.clone().expect("fields should named");
-            let slot_name = field.slot_name();
+            let slot_name = self.slot_name(field);
             let field_ty = field.return_ty();
             slots_tokens.append(quote! {
                fn #field_name(&self) -> #field_ty {
@@ -257,12 +271,24 @@ impl<'a> ToTokens for AssertedImpl<'a> {
                #slots_tokens
             }
         });
+        let template = self.template.as_str();
+        tokens.append(quote! {
+            impl<'a> #clips_crate::fact::FactType<'a> for #ident {
+               type Asserted = #name<'a>;
+               fn template_name() -> &'static str {
+                  #template
+               }
+               fn wrap(fact: #clips_crate::Fact<'a>) -> Self::Asserted {
+                  #name (fact)
+               }
+            }
+        });
         // implement Recoverable
         if !self.non_recoverable {
             let mut slot_tokens = Tokens::new();
             slot_tokens.append_separated(fields.iter().map(|field| {
                 let field_name = field.ident.clone().expect("fields should named");
-                let slot_name = field.slot_name();
+                let slot_name = self.slot_name(field);
                 quote!(#field_name: #clips_crate::ValueAccess::value(&self.slot(#slot_name)).unwrap())
             }), ",");
             tokens.append(quote! {
@@ -294,6 +320,9 @@ impl<'a> Deref for Assertable<'a> {
 
 impl<'a> ToTokens for Assertable<'a> {
     fn to_tokens(&self, tokens: &mut Tokens) {
+        if self.read_only {
+            return;
+        }
         let mut generics = self.generics.clone();
         if !self.consume_on_assert {
             generics.lifetimes.insert(0, syn::LifetimeDef::new("'__clips_assertable"));
@@ -315,24 +344,81 @@ impl<'a> ToTokens for Assertable<'a> {
         let mut slots_tokens = Tokens::new();
         for field in fields {
             let field_name = field.ident.clone().expect("fields should named");
-            let slot_name = field.slot_name();
+            let slot_name = self.slot_name(field);
             slots_tokens.append(quote! {
                 fb.put(#slot_name, self.#field_name()).or_else(|_| Err(()))?;
             });
         }
         let dummy_const = Ident::new(format!("_IMPL_ASSERTABLE_FOR_{}", ident));
         let template = self.template.as_str();
+        let (asserted_ty, asserted) = if self.run_on_assert {
+            (quote!(_clips::fact::Fired<#name<'__clips_env>>),
+             quote!(_clips::fact::Fired(#name(f), env.run(None))))
+        } else {
+            (quote!(#name<'__clips_env>), quote!(#name(f)))
+        };
         tokens.append(quote! {
             #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
              const #dummy_const: () = {
                 extern crate clips as _clips;
                 impl #imp _clips::fact::Assertable<'__clips_env> for #target_ident #ident #ty #wher {
-                   type T = #name<'__clips_env>;
+                   type T = #asserted_ty;
                    type Error = ();
                    fn assert(self, env: &'__clips_env _clips::Environment) -> Result<Self::T, Self::Error> {
                       let fb = env.new_fact_builder(#template);
                       #slots_tokens
-                      fb.assert().and_then(|f| Ok(#name(f))).or(Err(()))
+                      fb.assert().and_then(|f| Ok(#asserted)).or(Err(()))
+                   }
+                }
+             };
+        });
+    }
+}
+
+
+/// Display implementation for the struct, rendering it in CLIPS syntax
+struct DisplayImpl<'a>(&'a FactReceiver);
+
+impl<'a> Deref for DisplayImpl<'a> {
+    type Target = FactReceiver;
+
+    fn deref(&self) -> &Self::Target {
+        self.0
+    }
+}
+
+impl<'a> ToTokens for DisplayImpl<'a> {
+    fn to_tokens(&self, tokens: &mut Tokens) {
+        if !self.display {
+            return;
+        }
+        let (imp, ty, wher) = self.generics.split_for_impl();
+        let ident = &self.ident;
+        let fields = self.body.as_ref()
+            .take_struct()
+            .expect("Should never be enum")
+            .fields;
+        let mut slots_tokens = Tokens::new();
+        for field in fields {
+            let field_name = field.ident.clone().expect("fields should named");
+            let slot_name = format!(" ({} ", self.slot_name(field));
+            slots_tokens.append(quote! {
+                f.write_str(#slot_name)?;
+                _clips::value::ClipsDisplay::fmt_clips(&self.#field_name, f)?;
+                f.write_str(")")?;
+            });
+        }
+        let dummy_const = Ident::new(format!("_IMPL_DISPLAY_FOR_{}", ident));
+        let template = format!("({}", self.template);
+        tokens.append(quote! {
+            #[allow(non_upper_case_globals, unused_attributes, unused_qualifications)]
+             const #dummy_const: () = {
+                extern crate clips as _clips;
+                impl #imp ::std::fmt::Display for #ident #ty #wher {
+                   fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+                      f.write_str(#template)?;
+                      #slots_tokens
+                      f.write_str(")")
                    }
                 }
              };
@@ -340,18 +426,69 @@ impl<'a> ToTokens for Assertable<'a> {
     }
 }
 
+/// Checks if the name can be used as a CLIPS symbol
+/// (template names are symbols)
+fn is_valid_symbol(name: &str) -> bool {
+    clips::escape_symbol(name) == name
+}
 
+/// Derives fact support for a struct:
+///
+/// ```
+/// extern crate clips;
+/// #[macro_use] extern crate clips_derive;
+///
+/// #[derive(clips_fact)]
+/// #[clips(template="person")]
+/// struct Person {
+///     name: String,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The template name has to be a valid CLIPS symbol, otherwise compilation
+/// fails with "template name ... is not a valid CLIPS symbol":
+///
+/// ```compile_fail
+/// extern crate clips;
+/// #[macro_use] extern crate clips_derive;
+///
+/// #[derive(clips_fact)]
+/// #[clips(template="my person")]
+/// struct Person {
+///     name: String,
+/// }
+/// # fn main() {}
+/// ```
+///
+/// ```compile_fail
+/// extern crate clips;
+/// #[macro_use] extern crate clips_derive;
+///
+/// #[derive(clips_fact)]
+/// #[clips(template="")]
+/// struct Person {
+///     name: String,
+/// }
+/// # fn main() {}
+/// ```
 #[proc_macro_derive(clips_fact, attributes(clips))]
 pub fn derive_instruments(input: TokenStream) -> TokenStream {
     let input = syn::parse_derive_input(&input.to_string()).unwrap();
     let rcvr = FactReceiver::from_derive_input(&input).unwrap();
 
+    if !is_valid_symbol(&rcvr.template) {
+        let message = format!("clips_fact: template name {:?} is not a valid CLIPS symbol", rcvr.template);
+        return quote!(compile_error!(#message);).parse().unwrap();
+    }
+
     let slot_trait = SlotsTrait(&rcvr);
     let struct_impl = StructImpl(&rcvr);
     let asserted_impl = AssertedImpl(&rcvr);
     let assertable = Assertable(&rcvr);
+    let display = DisplayImpl(&rcvr);
 
-    let tokens = quote!( #slot_trait #struct_impl #asserted_impl #assertable);
+    let tokens = quote!( #slot_trait #struct_impl #asserted_impl #assertable #display);
 
     tokens.parse().unwrap()
 }
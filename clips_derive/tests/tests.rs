@@ -1,10 +1,10 @@
-extern crate clips;
+#[macro_use] extern crate clips;
 #[macro_use] extern crate clips_derive;
 
 use clips::fact::Assertable;
 
 #[derive(clips_fact)]
-#[clips(template="fact")]
+#[clips(template="fact",display)]
 struct Fact {
     test: String,
     #[clips(return_type="clone")]
@@ -147,3 +147,173 @@ fn casting() {
     let casted: AssertedCasting = fact.into();
     assert_eq!(casted.test(), 10);
 }
+
+#[derive(Debug, PartialEq, Clone, clips_fact)]
+#[clips(template="tpl")]
+struct Counts {
+    count: usize,
+    offset: isize,
+}
+
+#[test]
+fn usize_slots() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate tpl (slot count) (slot offset))").unwrap();
+    let counts = Counts {
+        count: 3,
+        offset: -1,
+    };
+    let f = counts.assert(&env).unwrap();
+    assert_eq!(f.count(), 3);
+    assert_eq!(f.offset(), -1);
+}
+
+#[test]
+fn display() {
+    let fact = Fact {
+        test: String::from("Hello"),
+        test1: String::from("Hi"),
+        i0: 0,
+    };
+    assert_eq!(fact.to_string(), r#"(fact (test "Hello") (test1 "Hi") (i0 0))"#);
+}
+
+#[test]
+fn facts_of() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate tpl (slot value) (slot value1))").unwrap();
+    env.load_string("(deftemplate other (slot value))").unwrap();
+    Rec { value: String::from("a"), value1: 1 }.assert(&env).unwrap();
+    Rec { value: String::from("b"), value1: 2 }.assert(&env).unwrap();
+    env.new_fact_builder("other").assert().unwrap();
+    let facts = env.facts_of::<Rec>();
+    assert_eq!(facts.len(), 2);
+    assert_eq!(facts[0].value(), "a");
+    assert_eq!(facts[1].value(), "b");
+}
+
+#[derive(clips_fact)]
+#[clips(template="event",run_on_assert)]
+struct Event {
+    name: String,
+}
+
+#[test]
+fn run_on_assert() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string(r#"
+    (deftemplate event (slot name))
+    (defrule on-event (event (name ?name)) => )
+    "#).unwrap();
+    let event = Event { name: String::from("started") };
+    let clips::fact::Fired(f, fired) = event.assert(&env).unwrap();
+    assert_eq!(fired, 1);
+    assert_eq!(f.name(), "started");
+}
+
+#[test]
+fn assert_unique() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate tpl (slot value) (slot value1))").unwrap();
+    let rec = Rec { value: String::from("a"), value1: 1 };
+    env.assert_unique(&rec).unwrap();
+    env.assert_unique(&rec).unwrap();
+    assert_eq!(env.number_of_facts(), 1);
+}
+
+#[derive(Debug, PartialEq, Clone, clips_fact)]
+#[clips(template="prefixed",slot_prefix="p_")]
+struct Prefixed {
+    test: String,
+    #[clips(rename="other")]
+    test1: i64,
+}
+
+#[test]
+fn slot_prefix() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate prefixed (slot p_test) (slot other))").unwrap();
+    let prefixed = Prefixed {
+        test: String::from("hello"),
+        test1: 1,
+    };
+    let f = prefixed.assert(&env).unwrap();
+    assert_eq!(clips::ValueAccess::value(&f.slot("other")) as Option<i64>, Some(1));
+    assert_eq!(f.test(), "hello");
+    assert_eq!(f.recover(), prefixed);
+}
+
+#[derive(clips_fact)]
+#[clips(template="reading",read_only)]
+struct Reading {
+    #[clips(return_type="clone")]
+    value: clips::OwnedValue,
+}
+
+#[test]
+fn read_only() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate reading (slot value))").unwrap();
+    env.eval("(assert (reading (value 1.5)))").unwrap();
+    env.eval("(assert (reading (value high)))").unwrap();
+    let readings = env.facts_of::<Reading>();
+    assert_eq!(readings.len(), 2);
+    assert_eq!(readings[0].value(), clips::OwnedValue::Float(1.5));
+    assert_eq!(readings[1].value(), clips::OwnedValue::Symbol(String::from("high")));
+}
+
+#[derive(clips_fact)]
+#[clips(template="person",read_only)]
+struct Person {
+    name: String,
+}
+
+#[derive(clips_fact)]
+#[clips(template="order",read_only)]
+struct Order {
+    amount: i64,
+}
+
+clips_fact_enum! {
+    enum AnyFact {
+        Person(Person),
+        Order(Order),
+    }
+}
+
+#[test]
+fn fact_enum() {
+    let env = clips::Environment::new().unwrap();
+    env.load_string("(deftemplate person (slot name)) (deftemplate order (slot amount)) (deftemplate other)").unwrap();
+    env.eval("(assert (person (name \"Alice\")))").unwrap();
+    env.eval("(assert (order (amount 10)))").unwrap();
+    env.eval("(assert (other))").unwrap();
+    let mut names = vec![];
+    let mut total = 0;
+    let mut others = 0;
+    for fact in env.fact_iter() {
+        match AnyFact::from(fact) {
+            AnyFact::Person(person) => names.push(String::from(person.name())),
+            AnyFact::Order(order) => total += order.amount(),
+            AnyFact::Other(_) => others += 1,
+        }
+    }
+    assert_eq!(names, vec!["Alice"]);
+    assert_eq!(total, 10);
+    assert_eq!(others, 1);
+}
+
+// names that Rust would parse as floats are valid CLIPS symbols
+#[derive(clips_fact)]
+#[clips(template="inf")]
+struct Inf {
+    a: i64,
+}
+
+#[test]
+fn float_like_template_name() {
+    use clips::fact::FactType;
+    assert_eq!(<Inf as FactType>::template_name(), "inf");
+    let inf = Inf { a: 1 };
+    assert_eq!(inf.a(), 1);
+}
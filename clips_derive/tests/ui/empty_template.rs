@@ -0,0 +1,7 @@
+#[macro_use] extern crate clips_derive;
+
+#[derive(clips_fact)]
+#[clips(template="")]
+struct Empty {}
+
+fn main() {}